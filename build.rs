@@ -1,9 +1,10 @@
-use sqlx::sqlite::SqlitePoolOptions;
-use std::path::PathBuf;
-use std::{env, fs};
-
+#[cfg(not(feature = "postgres"))]
 #[tokio::main]
 async fn main() {
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::path::PathBuf;
+    use std::{env, fs};
+
     let out_dir: PathBuf = env::var("OUT_DIR").unwrap().into();
     let db_path = out_dir.join("build.db");
 
@@ -22,3 +23,62 @@ async fn main() {
 
     println!("cargo:rustc-env=DATABASE_URL=sqlite:{}", db_url);
 }
+
+// The queries are checked against a throwaway database of their own,
+// which is created on the server of `BERNARD_BUILD_DATABASE_URL` and migrated again on every build.
+// The database of the URL itself is never modified.
+#[cfg(feature = "postgres")]
+#[tokio::main]
+async fn main() {
+    use sqlx::postgres::PgPoolOptions;
+    use std::collections::hash_map::DefaultHasher;
+    use std::env;
+    use std::hash::{Hash, Hasher};
+    use url::Url;
+
+    println!("cargo:rerun-if-changed=migrations-postgres");
+    println!("cargo:rerun-if-env-changed=BERNARD_BUILD_DATABASE_URL");
+
+    let server_url = env::var("BERNARD_BUILD_DATABASE_URL")
+        .expect("BERNARD_BUILD_DATABASE_URL must refer to a Postgres server which allows creating databases");
+
+    // Every build directory has a database of its own, so concurrent builds do not interfere.
+    let mut hasher = DefaultHasher::new();
+    env::var("OUT_DIR").unwrap().hash(&mut hasher);
+    let database = format!("bernard_build_{:016x}", hasher.finish());
+
+    let pool = PgPoolOptions::default()
+        .max_connections(1)
+        .connect(&server_url)
+        .await
+        .unwrap();
+
+    sqlx::query(&format!("DROP DATABASE IF EXISTS {}", database))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    sqlx::query(&format!("CREATE DATABASE {}", database))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    pool.close().await;
+
+    let mut db_url = Url::parse(&server_url).unwrap();
+    db_url.set_path(&database);
+
+    let pool = PgPoolOptions::default()
+        .connect(db_url.as_str())
+        .await
+        .unwrap();
+
+    sqlx::migrate!("./migrations-postgres")
+        .run(&pool)
+        .await
+        .unwrap();
+
+    pool.close().await;
+
+    println!("cargo:rustc-env=DATABASE_URL={}", db_url);
+}
@@ -0,0 +1,60 @@
+use anyhow::{bail, Result};
+use bernard::{Difference, ExportFormat, Snapshot};
+use clap::Clap;
+
+/// Compare two Bernard databases or exports of the same Shared Drive.
+#[derive(Clap)]
+#[clap(name = "diff")]
+struct Opt {
+    /// The older database (.db) or export (.ndjson, .csv or .json)
+    #[clap(value_name = "OLD")]
+    old: String,
+
+    /// The newer database (.db) or export (.ndjson, .csv or .json)
+    #[clap(value_name = "NEW")]
+    new: String,
+
+    /// Shared Drive ID to compare, required for databases
+    #[clap(short, long = "drive", value_name = "ID")]
+    drive_id: Option<String>,
+}
+
+async fn open(path: &str, drive_id: Option<&str>) -> Result<Snapshot> {
+    let format = match path.rsplit('.').next() {
+        Some("ndjson") | Some("jsonl") => ExportFormat::Ndjson,
+        Some("csv") => ExportFormat::Csv,
+        Some("json") => ExportFormat::Json,
+        _ => match drive_id {
+            Some(drive_id) => return Ok(Snapshot::from_database(path, drive_id).await?),
+            None => bail!("--drive is required to read the database {}", path),
+        },
+    };
+
+    let file = tokio::fs::File::open(path).await?;
+    Ok(Snapshot::from_export(format, file).await?)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Parse the CLI arguments.
+    let opt = Opt::parse();
+
+    let old = open(&opt.old, opt.drive_id.as_deref()).await?;
+    let new = open(&opt.new, opt.drive_id.as_deref()).await?;
+
+    // Print the differences in the style of a unified diff.
+    for difference in old.diff(&new)? {
+        let path = |path: &Option<String>| path.clone().unwrap_or_else(|| "?".to_owned());
+
+        match difference {
+            Difference::Created(record) => println!("+ {}", path(&record.path)),
+            Difference::Deleted(record) => println!("- {}", path(&record.path)),
+            Difference::Moved { old, new } => {
+                println!("> {} -> {}", path(&old.path), path(&new.path))
+            }
+            Difference::Modified { new, .. } => println!("~ {}", path(&new.path)),
+        }
+    }
+
+    Ok(())
+}
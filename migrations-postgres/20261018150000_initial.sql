@@ -0,0 +1,276 @@
+-- The PostgreSQL port of the SQLite migrations up to and including `history`.
+-- Text is compared byte by byte, like SQLite, which the prefix scans of paths rely on.
+
+-- Models
+CREATE TABLE drives (
+    id TEXT COLLATE "C" NOT NULL,
+    page_token TEXT NOT NULL,
+    PRIMARY KEY(id)
+);
+
+CREATE TABLE folders (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    parent TEXT COLLATE "C",
+    PRIMARY KEY(id, drive_id),
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE,
+    -- Deferred constraint so integrity is checked at the end of the transaction.
+    FOREIGN KEY(parent, drive_id) REFERENCES folders(id, drive_id) ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED
+);
+
+-- Create an index on the parent. This massively speeds up the FK constraint.
+CREATE INDEX folders_parent ON folders (parent, drive_id);
+
+CREATE TABLE files (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    parent TEXT COLLATE "C" NOT NULL,
+    md5 TEXT COLLATE "C" NOT NULL,
+    size BIGINT NOT NULL,
+    PRIMARY KEY(id, drive_id),
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE,
+    -- Deferred constraint so integrity is checked at the end of the transaction.
+    FOREIGN KEY(parent, drive_id) REFERENCES folders(id, drive_id) ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED
+);
+
+-- Create an index on the parent. This massively speeds up the FK constraint.
+CREATE INDEX files_parent ON files (parent, drive_id);
+
+-- Index to group the files by their content.
+CREATE INDEX files_size_md5 ON files (size, md5);
+
+-- Changelogs
+CREATE TABLE folder_changelog (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    deleted BOOLEAN NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    parent TEXT COLLATE "C",
+    PRIMARY KEY(id, drive_id, deleted)
+);
+
+CREATE TABLE file_changelog (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    deleted BOOLEAN NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    parent TEXT COLLATE "C" NOT NULL,
+    md5 TEXT COLLATE "C" NOT NULL,
+    size BIGINT NOT NULL,
+    PRIMARY KEY(id, drive_id, deleted)
+);
+
+-- Folder triggers
+CREATE FUNCTION folder_delete() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO folder_changelog (id, drive_id, deleted, name, trashed, parent)
+    VALUES (OLD.id, OLD.drive_id, TRUE, OLD.name, OLD.trashed, OLD.parent);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER folder_delete
+AFTER DELETE ON folders
+FOR EACH ROW EXECUTE FUNCTION folder_delete();
+
+CREATE FUNCTION folder_update() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO folder_changelog (id, drive_id, deleted, name, trashed, parent)
+    VALUES
+        (OLD.id, OLD.drive_id, TRUE, OLD.name, OLD.trashed, OLD.parent),
+        (NEW.id, NEW.drive_id, FALSE, NEW.name, NEW.trashed, NEW.parent);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER folder_update
+AFTER UPDATE ON folders
+FOR EACH ROW
+WHEN (OLD.name <> NEW.name OR OLD.trashed <> NEW.trashed OR OLD.parent <> NEW.parent)
+EXECUTE FUNCTION folder_update();
+
+CREATE FUNCTION folder_create() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO folder_changelog (id, drive_id, deleted, name, trashed, parent)
+    VALUES (NEW.id, NEW.drive_id, FALSE, NEW.name, NEW.trashed, NEW.parent);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER folder_create
+AFTER INSERT ON folders
+FOR EACH ROW EXECUTE FUNCTION folder_create();
+
+-- File triggers
+CREATE FUNCTION file_delete() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO file_changelog (id, drive_id, deleted, name, trashed, parent, md5, size)
+    VALUES (OLD.id, OLD.drive_id, TRUE, OLD.name, OLD.trashed, OLD.parent, OLD.md5, OLD.size);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER file_delete
+AFTER DELETE ON files
+FOR EACH ROW EXECUTE FUNCTION file_delete();
+
+CREATE FUNCTION file_update() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO file_changelog (id, drive_id, deleted, name, trashed, parent, md5, size)
+    VALUES
+        (OLD.id, OLD.drive_id, TRUE, OLD.name, OLD.trashed, OLD.parent, OLD.md5, OLD.size),
+        (NEW.id, NEW.drive_id, FALSE, NEW.name, NEW.trashed, NEW.parent, NEW.md5, NEW.size);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER file_update
+AFTER UPDATE ON files
+FOR EACH ROW
+WHEN (OLD.name <> NEW.name OR OLD.trashed <> NEW.trashed OR OLD.parent <> NEW.parent OR OLD.md5 <> NEW.md5 OR OLD.size <> NEW.size)
+EXECUTE FUNCTION file_update();
+
+CREATE FUNCTION file_create() RETURNS TRIGGER AS $$
+BEGIN
+    INSERT INTO file_changelog (id, drive_id, deleted, name, trashed, parent, md5, size)
+    VALUES (NEW.id, NEW.drive_id, FALSE, NEW.name, NEW.trashed, NEW.parent, NEW.md5, NEW.size);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER file_create
+AFTER INSERT ON files
+FOR EACH ROW EXECUTE FUNCTION file_create();
+
+-- Materialise the paths of all files and folders.
+-- The paths are maintained by Bernard whenever changes are merged.
+CREATE TABLE item_paths (
+    key BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    folder BOOLEAN NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    path TEXT COLLATE "C" NOT NULL,
+    -- Full-text index over the name, everything after the last slash, and the path.
+    search TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', regexp_replace(path, '^.*/', '')), 'A')
+        || to_tsvector('simple', path)
+    ) STORED,
+    UNIQUE(id, drive_id),
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE
+);
+
+-- Index for lookups and prefix scans of paths.
+CREATE INDEX item_paths_path ON item_paths (drive_id, path);
+
+CREATE INDEX item_paths_search ON item_paths USING GIN (search);
+
+-- Views
+CREATE VIEW paths AS
+    SELECT p.folder, p.id, p.drive_id, p.path FROM item_paths p;
+
+CREATE VIEW path_changelog AS
+    WITH RECURSIVE
+        changelog_paths AS (
+            -- Initial folders
+            SELECT TRUE as folder, f.id, f.drive_id, f.parent, f.deleted, f.trashed, '/' || f.name as path FROM folder_changelog f
+
+            UNION ALL
+
+            -- Initial files
+            SELECT FALSE as folder, f.id, f.drive_id, f.parent, f.deleted, f.trashed, '/' || f.name as path FROM file_changelog f
+
+            UNION ALL
+
+            -- Recursive clause (using p.id to preserve original id)
+            SELECT p.folder, p.id, f.drive_id, f.parent, f.deleted, f.trashed, '/' || f.name || p.path as path
+            FROM folder_changelog f, changelog_paths p
+            WHERE f.id = p.parent AND f.drive_id = p.drive_id
+        ),
+        full_paths AS (
+            -- Initial changed paths
+            SELECT p.folder, p.id, p.drive_id, p.parent, p.deleted, p.trashed, p.path FROM changelog_paths p
+            -- Not exists to only get the "full" path of each id.
+            WHERE NOT EXISTS (
+                SELECT * FROM changelog_paths p2
+                WHERE p2.id = p.parent
+            )
+
+            UNION ALL
+
+            -- Recursive clause
+            SELECT p.folder, p.id, f.drive_id, f.parent, p.deleted, p.trashed, '/' || f.name || p.path as path
+            FROM folders f
+            INNER JOIN full_paths p ON f.id = p.parent AND f.drive_id = p.drive_id
+            WHERE f.parent IS NOT NULL
+        )
+    SELECT p.folder, p.id, p.drive_id, p.deleted, p.trashed, p.path FROM full_paths p
+    WHERE p.parent = p.drive_id;
+
+-- Recursive totals of each folder, which ignore trashed items and everything within them.
+CREATE TABLE folder_stats (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    size BIGINT NOT NULL,
+    files BIGINT NOT NULL,
+    folders BIGINT NOT NULL,
+    -- Set while the totals are being recomputed.
+    dirty BOOLEAN NOT NULL,
+    PRIMARY KEY(id, drive_id),
+    FOREIGN KEY(id, drive_id) REFERENCES folders(id, drive_id) ON DELETE CASCADE
+);
+
+-- Records of deleted files and folders, which outlive the changelog.
+CREATE TABLE tombstones (
+    key BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    folder BOOLEAN NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    parent TEXT COLLATE "C",
+    -- The last known path, unknown if the item was not reachable from the root folder.
+    path TEXT COLLATE "C",
+    deleted_at TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE
+);
+
+CREATE INDEX tombstones_deleted_at ON tombstones (drive_id, deleted_at);
+
+-- Every committed synchronisation of a Shared Drive.
+CREATE TABLE sync_runs (
+    id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    page_token TEXT NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL,
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE
+);
+
+CREATE INDEX sync_runs_synced_at ON sync_runs (drive_id, synced_at);
+
+-- Every version of a file or folder, valid from the run which created it
+-- up to the run which replaced or deleted it.
+CREATE TABLE item_history (
+    key BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    folder BOOLEAN NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    parent TEXT COLLATE "C",
+    md5 TEXT COLLATE "C",
+    size BIGINT,
+    valid_from BIGINT NOT NULL,
+    valid_to BIGINT,
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE,
+    FOREIGN KEY(valid_from) REFERENCES sync_runs(id),
+    FOREIGN KEY(valid_to) REFERENCES sync_runs(id)
+);
+
+CREATE INDEX item_history_id ON item_history (id, drive_id, valid_to);
+CREATE INDEX item_history_parent ON item_history (parent, drive_id, valid_from);
@@ -0,0 +1,16 @@
+-- Files and folders of which the parent was unknown when their change was merged.
+-- They are retried on every merge, until their parent is known.
+CREATE TABLE quarantine (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    folder BOOLEAN NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    parent TEXT COLLATE "C" NOT NULL,
+    md5 TEXT COLLATE "C",
+    size BIGINT,
+    reason TEXT NOT NULL,
+    quarantined_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY(id, drive_id),
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE
+);
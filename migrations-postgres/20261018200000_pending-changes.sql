@@ -0,0 +1,23 @@
+-- The page token of the change list which has been rejected by the safeguard, if any.
+ALTER TABLE drives ADD COLUMN pending_page_token TEXT;
+
+-- The change list which has been rejected by the safeguard, until it is accepted or superseded.
+CREATE TABLE pending_changes (
+    drive_id TEXT COLLATE "C" NOT NULL,
+    -- The position of the change within the change list.
+    position BIGINT NOT NULL,
+    -- The id of the changed drive or item.
+    id TEXT COLLATE "C" NOT NULL,
+    drive BOOLEAN NOT NULL,
+    removed BOOLEAN NOT NULL,
+    -- The metadata of a changed drive or item, NULL if it has been removed.
+    folder BOOLEAN,
+    item_drive_id TEXT COLLATE "C",
+    name TEXT COLLATE "C",
+    trashed BOOLEAN,
+    parent TEXT COLLATE "C",
+    md5 TEXT COLLATE "C",
+    size BIGINT,
+    PRIMARY KEY(drive_id, position),
+    FOREIGN KEY(drive_id) REFERENCES drives(id) ON DELETE CASCADE
+);
@@ -0,0 +1,36 @@
+-- The paths of the changelog are recorded whenever the materialised paths are refreshed,
+-- rather than derived by the path_changelog view for every page of the changelog.
+-- The view joined a changed item with every version of its changed ancestors,
+-- hence renaming a folder and an item within it yielded a path per version of the folder.
+CREATE TABLE changelog_paths_new (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    deleted BOOLEAN NOT NULL,
+    folder BOOLEAN NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    path TEXT COLLATE "C" NOT NULL,
+    PRIMARY KEY(id, drive_id, deleted)
+);
+
+-- Keep the current changelog, of which the disambiguated paths take precedence.
+INSERT INTO changelog_paths_new (id, drive_id, deleted, folder, trashed, path)
+    SELECT c.id, c.drive_id, c.deleted, c.folder, c.trashed, COALESCE(o.path, c.path)
+    FROM path_changelog c
+    LEFT JOIN changelog_paths o ON o.id = c.id AND o.drive_id = c.drive_id AND o.deleted = c.deleted
+    ON CONFLICT DO NOTHING;
+
+DROP TABLE changelog_paths;
+ALTER TABLE changelog_paths_new RENAME TO changelog_paths;
+
+DROP VIEW path_changelog;
+
+-- The paths replaced by a refresh, until the paths of the changelog have been recorded.
+CREATE TABLE stale_paths (
+    id TEXT COLLATE "C" NOT NULL,
+    drive_id TEXT COLLATE "C" NOT NULL,
+    folder BOOLEAN NOT NULL,
+    trashed BOOLEAN NOT NULL,
+    name TEXT NOT NULL,
+    path TEXT COLLATE "C" NOT NULL,
+    PRIMARY KEY(id, drive_id)
+);
@@ -0,0 +1,23 @@
+-- The name of every item next to its path, as names may contain slashes
+-- and disambiguated paths suffix the names with the id of the item.
+ALTER TABLE item_paths ADD COLUMN name TEXT NOT NULL DEFAULT '';
+
+UPDATE item_paths p SET name = COALESCE(
+    (SELECT f.name FROM folders f WHERE p.folder AND f.id = p.id AND f.drive_id = p.drive_id),
+    (SELECT f.name FROM files f WHERE NOT p.folder AND f.id = p.id AND f.drive_id = p.drive_id),
+    ''
+);
+
+ALTER TABLE item_paths ALTER COLUMN name DROP DEFAULT;
+
+-- Full-text index over the name and the path.
+-- The words are the runs of letters and digits, like the FTS5 tokenizer of SQLite splits them,
+-- rather than the file names and paths recognised by the parser of Postgres.
+ALTER TABLE item_paths DROP COLUMN search;
+
+ALTER TABLE item_paths ADD COLUMN search TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('simple', regexp_replace(name, '[^[:alnum:]]+', ' ', 'g')), 'A')
+    || to_tsvector('simple', regexp_replace(path, '[^[:alnum:]]+', ' ', 'g'))
+) STORED;
+
+CREATE INDEX item_paths_search ON item_paths USING GIN (search);
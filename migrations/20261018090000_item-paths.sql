@@ -0,0 +1,25 @@
+-- Materialise the paths of all files and folders.
+-- The paths are maintained by Bernard whenever changes are merged.
+CREATE TABLE item_paths (
+    'id' TEXT NOT NULL,
+    'drive_id' TEXT NOT NULL,
+    'folder' BOOLEAN NOT NULL,
+    'trashed' BOOLEAN NOT NULL,
+    'path' TEXT NOT NULL,
+    PRIMARY KEY('id', 'drive_id'),
+    FOREIGN KEY('drive_id') REFERENCES drives('id') ON DELETE CASCADE
+);
+
+-- Index for lookups and prefix scans of paths.
+CREATE INDEX item_paths_path ON item_paths ('drive_id', 'path');
+
+INSERT INTO item_paths ('id', 'drive_id', 'folder', 'trashed', 'path')
+    SELECT p.id, p.drive_id, p.folder, COALESCE(fo.trashed, fi.trashed), p.path FROM paths p
+    LEFT JOIN folders fo ON p.folder = 1 AND fo.id = p.id AND fo.drive_id = p.drive_id
+    LEFT JOIN files fi ON p.folder = 0 AND fi.id = p.id AND fi.drive_id = p.drive_id;
+
+-- The `paths` view no longer has to compute the paths itself.
+DROP VIEW paths;
+
+CREATE VIEW paths AS
+    SELECT p.folder, p.id, p.drive_id, p.path FROM item_paths p;
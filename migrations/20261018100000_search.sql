@@ -0,0 +1,58 @@
+-- The full-text index refers to `item_paths` by rowid.
+-- VACUUM may change rowids, unless they are an explicit INTEGER PRIMARY KEY.
+DROP VIEW paths;
+
+CREATE TABLE item_paths_new (
+    'key' INTEGER PRIMARY KEY,
+    'id' TEXT NOT NULL,
+    'drive_id' TEXT NOT NULL,
+    'folder' BOOLEAN NOT NULL,
+    'trashed' BOOLEAN NOT NULL,
+    'path' TEXT NOT NULL,
+    UNIQUE('id', 'drive_id'),
+    FOREIGN KEY('drive_id') REFERENCES drives('id') ON DELETE CASCADE
+);
+
+INSERT INTO item_paths_new ('id', 'drive_id', 'folder', 'trashed', 'path')
+    SELECT p.id, p.drive_id, p.folder, p.trashed, p.path FROM item_paths p;
+
+DROP TABLE item_paths;
+ALTER TABLE item_paths_new RENAME TO item_paths;
+
+CREATE INDEX item_paths_path ON item_paths ('drive_id', 'path');
+
+CREATE VIEW paths AS
+    SELECT p.folder, p.id, p.drive_id, p.path FROM item_paths p;
+
+-- Full-text index over the names and paths of all files and folders.
+-- Contentless, as the content is already stored in `item_paths`.
+CREATE VIRTUAL TABLE item_search USING fts5('name', 'path', content='');
+
+-- The name is everything after the last slash of the path.
+INSERT INTO item_search ('rowid', 'name', 'path')
+    SELECT p.key, substr(p.path, length(rtrim(p.path, replace(p.path, '/', ''))) + 1), p.path
+    FROM item_paths p;
+
+CREATE TRIGGER item_search_insert
+AFTER INSERT ON item_paths
+BEGIN
+    INSERT INTO item_search ('rowid', 'name', 'path')
+    VALUES (NEW.key, substr(NEW.path, length(rtrim(NEW.path, replace(NEW.path, '/', ''))) + 1), NEW.path);
+END;
+
+CREATE TRIGGER item_search_update
+AFTER UPDATE OF path ON item_paths
+BEGIN
+    INSERT INTO item_search ('item_search', 'rowid', 'name', 'path')
+    VALUES ('delete', OLD.key, substr(OLD.path, length(rtrim(OLD.path, replace(OLD.path, '/', ''))) + 1), OLD.path);
+
+    INSERT INTO item_search ('rowid', 'name', 'path')
+    VALUES (NEW.key, substr(NEW.path, length(rtrim(NEW.path, replace(NEW.path, '/', ''))) + 1), NEW.path);
+END;
+
+CREATE TRIGGER item_search_delete
+AFTER DELETE ON item_paths
+BEGIN
+    INSERT INTO item_search ('item_search', 'rowid', 'name', 'path')
+    VALUES ('delete', OLD.key, substr(OLD.path, length(rtrim(OLD.path, replace(OLD.path, '/', ''))) + 1), OLD.path);
+END;
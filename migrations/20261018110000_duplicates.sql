@@ -0,0 +1,2 @@
+-- Index to group the files by their content.
+CREATE INDEX files_size_md5 ON files ('size', 'md5');
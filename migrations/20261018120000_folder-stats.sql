@@ -0,0 +1,34 @@
+-- Recursive totals of each folder, which ignore trashed items and everything within them.
+CREATE TABLE folder_stats (
+    'id' TEXT NOT NULL,
+    'drive_id' TEXT NOT NULL,
+    'size' BIGINT NOT NULL,
+    'files' BIGINT NOT NULL,
+    'folders' BIGINT NOT NULL,
+    -- Set while the totals are being recomputed.
+    'dirty' BOOLEAN NOT NULL,
+    PRIMARY KEY('id', 'drive_id'),
+    FOREIGN KEY('id', 'drive_id') REFERENCES folders('id', 'drive_id') ON DELETE CASCADE
+);
+
+INSERT INTO folder_stats ('id', 'drive_id', 'size', 'files', 'folders', 'dirty')
+    WITH RECURSIVE tree(root, id, drive_id) AS (
+        SELECT f.id, f.id, f.drive_id FROM folders f
+
+        UNION ALL
+
+        SELECT t.root, f.id, f.drive_id
+        FROM folders f
+        INNER JOIN tree t ON f.parent = t.id AND f.drive_id = t.drive_id
+        WHERE NOT f.trashed
+    )
+    SELECT
+        t.root,
+        t.drive_id,
+        COALESCE(SUM(f.size), 0),
+        COUNT(f.id),
+        (SELECT COUNT(*) FROM tree t2 WHERE t2.root = t.root AND t2.drive_id = t.drive_id) - 1,
+        0
+    FROM tree t
+    LEFT JOIN files f ON f.parent = t.id AND f.drive_id = t.drive_id AND NOT f.trashed
+    GROUP BY t.root, t.drive_id;
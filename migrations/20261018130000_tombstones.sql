@@ -0,0 +1,15 @@
+-- Records of deleted files and folders, which outlive the changelog.
+CREATE TABLE tombstones (
+    'key' INTEGER PRIMARY KEY,
+    'id' TEXT NOT NULL,
+    'drive_id' TEXT NOT NULL,
+    'folder' BOOLEAN NOT NULL,
+    'name' TEXT NOT NULL,
+    'parent' TEXT,
+    -- The last known path, unknown if the item was not reachable from the root folder.
+    'path' TEXT,
+    'deleted_at' DATETIME NOT NULL,
+    FOREIGN KEY('drive_id') REFERENCES drives('id') ON DELETE CASCADE
+);
+
+CREATE INDEX tombstones_deleted_at ON tombstones ('drive_id', 'deleted_at');
@@ -0,0 +1,43 @@
+-- Every committed synchronisation of a Shared Drive.
+CREATE TABLE sync_runs (
+    'id' INTEGER PRIMARY KEY,
+    'drive_id' TEXT NOT NULL,
+    'page_token' TEXT NOT NULL,
+    'synced_at' DATETIME NOT NULL,
+    FOREIGN KEY('drive_id') REFERENCES drives('id') ON DELETE CASCADE
+);
+
+CREATE INDEX sync_runs_synced_at ON sync_runs ('drive_id', 'synced_at');
+
+-- Every version of a file or folder, valid from the run which created it
+-- up to the run which replaced or deleted it.
+CREATE TABLE item_history (
+    'key' INTEGER PRIMARY KEY,
+    'id' TEXT NOT NULL,
+    'drive_id' TEXT NOT NULL,
+    'folder' BOOLEAN NOT NULL,
+    'name' TEXT NOT NULL,
+    'trashed' BOOLEAN NOT NULL,
+    'parent' TEXT,
+    'md5' TEXT,
+    'size' BIGINT,
+    'valid_from' INTEGER NOT NULL,
+    'valid_to' INTEGER,
+    FOREIGN KEY('drive_id') REFERENCES drives('id') ON DELETE CASCADE,
+    FOREIGN KEY('valid_from') REFERENCES sync_runs('id'),
+    FOREIGN KEY('valid_to') REFERENCES sync_runs('id')
+);
+
+CREATE INDEX item_history_id ON item_history ('id', 'drive_id', 'valid_to');
+CREATE INDEX item_history_parent ON item_history ('parent', 'drive_id', 'valid_from');
+
+-- The history of existing drives starts at this migration.
+INSERT INTO sync_runs ('drive_id', 'page_token', 'synced_at')
+    SELECT d.id, d.page_token, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') FROM drives d;
+
+INSERT INTO item_history ('id', 'drive_id', 'folder', 'name', 'trashed', 'parent', 'md5', 'size', 'valid_from')
+    SELECT f.id, f.drive_id, 1, f.name, f.trashed, f.parent, NULL, NULL, r.id FROM folders f
+    INNER JOIN sync_runs r ON r.drive_id = f.drive_id
+    UNION ALL
+    SELECT f.id, f.drive_id, 0, f.name, f.trashed, f.parent, f.md5, f.size, r.id FROM files f
+    INNER JOIN sync_runs r ON r.drive_id = f.drive_id;
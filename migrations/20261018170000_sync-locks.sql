@@ -0,0 +1,9 @@
+-- Leases on the synchronisation of a Shared Drive, shared by all processes using the database.
+-- The drive might not exist yet, as the lease is already taken for the full synchronisation.
+CREATE TABLE sync_locks (
+    'drive_id' TEXT NOT NULL,
+    'holder' TEXT NOT NULL,
+    -- Milliseconds since the Unix epoch, after which another holder may take over.
+    'expires_at' BIGINT NOT NULL,
+    PRIMARY KEY('drive_id')
+);
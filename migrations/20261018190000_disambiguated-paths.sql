@@ -0,0 +1,13 @@
+-- Whether the materialised paths of a drive suffix the names shared by siblings with their id.
+ALTER TABLE drives ADD COLUMN 'disambiguate_paths' BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- The disambiguated paths of the changelog, which the path_changelog view cannot derive.
+-- The deleted paths are recorded before the materialised paths are refreshed,
+-- the created paths after.
+CREATE TABLE changelog_paths (
+    'id' TEXT NOT NULL,
+    'drive_id' TEXT NOT NULL,
+    'deleted' BOOLEAN NOT NULL,
+    'path' TEXT NOT NULL,
+    PRIMARY KEY('id', 'drive_id', 'deleted')
+);
@@ -0,0 +1,43 @@
+-- The name of every item next to its path, as names may contain slashes
+-- and disambiguated paths suffix the names with the id of the item.
+ALTER TABLE item_paths ADD COLUMN 'name' TEXT NOT NULL DEFAULT '';
+
+UPDATE item_paths SET 'name' = COALESCE(
+    (SELECT f.name FROM folders f WHERE item_paths.folder = 1 AND f.id = item_paths.id AND f.drive_id = item_paths.drive_id),
+    (SELECT f.name FROM files f WHERE item_paths.folder = 0 AND f.id = item_paths.id AND f.drive_id = item_paths.drive_id),
+    ''
+);
+
+-- Index the names rather than everything after the last slash of the paths.
+DROP TRIGGER item_search_insert;
+DROP TRIGGER item_search_update;
+DROP TRIGGER item_search_delete;
+
+INSERT INTO item_search ('item_search') VALUES ('delete-all');
+
+INSERT INTO item_search ('rowid', 'name', 'path')
+    SELECT p.key, p.name, p.path FROM item_paths p;
+
+CREATE TRIGGER item_search_insert
+AFTER INSERT ON item_paths
+BEGIN
+    INSERT INTO item_search ('rowid', 'name', 'path')
+    VALUES (NEW.key, NEW.name, NEW.path);
+END;
+
+CREATE TRIGGER item_search_update
+AFTER UPDATE OF name, path ON item_paths
+BEGIN
+    INSERT INTO item_search ('item_search', 'rowid', 'name', 'path')
+    VALUES ('delete', OLD.key, OLD.name, OLD.path);
+
+    INSERT INTO item_search ('rowid', 'name', 'path')
+    VALUES (NEW.key, NEW.name, NEW.path);
+END;
+
+CREATE TRIGGER item_search_delete
+AFTER DELETE ON item_paths
+BEGIN
+    INSERT INTO item_search ('item_search', 'rowid', 'name', 'path')
+    VALUES ('delete', OLD.key, OLD.name, OLD.path);
+END;
@@ -1,35 +1,230 @@
-use crate::{database, Bernard, ChangedFile, ChangedFolder, ChangedPath, Result};
+use crate::lock::ReadGuard;
+use crate::model::ChangelogKey;
+use crate::store::{Changelog, Store};
+use crate::{ChangedFile, ChangedFolder, ChangedPath, DatabaseStore, InnerPath, Result};
+use futures::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use tokio::sync::Mutex;
 
-// Opportunity: Changes could hold the transaction to ensure it reflects the current database state.
-// To make this work, the *actual* transaction would use a savepoint.
-pub struct Changes<'a> {
-    bernard: &'a Bernard,
+/// The amount of changes fetched per query when streaming.
+pub const PAGE_SIZE: i64 = 1000;
+
+/// The changes of the most recent synchronisation of a Shared Drive.
+///
+/// `Changes` hold a snapshot of the changelog, such as a read transaction,
+/// so all views agree with each other even if the store is modified in the meantime.
+/// Synchronising the same Shared Drive fails until the `Changes` are dropped.
+///
+/// With a [`DatabaseStore`], the read transaction keeps a connection of the pool
+/// for as long as the `Changes` are alive, so drop them once they have been read.
+/// An in-memory SQLite database has a single connection,
+/// so any other use of it fails with a timeout until the `Changes` are dropped.
+pub struct Changes<'a, S: Store = DatabaseStore> {
     drive_id: &'a str,
+    changelog: Mutex<S::Changelog>,
+    _guard: Option<ReadGuard>,
 }
 
-impl<'a> Changes<'a> {
-    pub(crate) fn new(bernard: &'a Bernard, drive_id: &'a str) -> Self {
-        Self { bernard, drive_id }
+impl<'a, S: Store> Changes<'a, S> {
+    pub(crate) fn new(
+        drive_id: &'a str,
+        changelog: S::Changelog,
+        guard: Option<ReadGuard>,
+    ) -> Self {
+        Self {
+            drive_id,
+            changelog: Mutex::new(changelog),
+            _guard: guard,
+        }
+    }
+
+    /// The Shared Drive of which these are the changes.
+    pub fn drive_id(&self) -> &str {
+        self.drive_id
     }
 
     #[tracing::instrument(level = "trace", skip(self), fields(self.drive_id))]
     pub async fn paths(&self) -> Result<Vec<ChangedPath>> {
-        database::get_changed_paths(self.drive_id, &self.bernard.pool)
-            .await
-            .map_err(|e| e.into())
+        self.stream_paths().try_collect().await
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
     pub async fn folders(&self) -> Result<Vec<ChangedFolder>> {
-        database::get_changed_folders(self.drive_id, &self.bernard.pool)
-            .await
-            .map_err(|e| e.into())
+        self.stream_folders().try_collect().await
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
     pub async fn files(&self) -> Result<Vec<ChangedFile>> {
-        database::get_changed_files(self.drive_id, &self.bernard.pool)
-            .await
-            .map_err(|e| e.into())
+        self.stream_files().try_collect().await
+    }
+
+    /// Stream the changed paths, fetching at most `PAGE_SIZE` paths at a time.
+    pub fn stream_paths(&self) -> impl Stream<Item = Result<ChangedPath>> + '_ {
+        paginate(ChangedPath::key, move |after| async move {
+            let mut changelog = self.changelog.lock().await;
+            changelog.get_changed_paths(after.as_ref(), PAGE_SIZE).await
+        })
+    }
+
+    /// Stream the changed folders, fetching at most `PAGE_SIZE` folders at a time.
+    pub fn stream_folders(&self) -> impl Stream<Item = Result<ChangedFolder>> + '_ {
+        paginate(ChangedFolder::key, move |after| async move {
+            let mut changelog = self.changelog.lock().await;
+            changelog
+                .get_changed_folders(after.as_ref(), PAGE_SIZE)
+                .await
+        })
+    }
+
+    /// Stream the changed files, fetching at most `PAGE_SIZE` files at a time.
+    pub fn stream_files(&self) -> impl Stream<Item = Result<ChangedFile>> + '_ {
+        paginate(ChangedFile::key, move |after| async move {
+            let mut changelog = self.changelog.lock().await;
+            changelog.get_changed_files(after.as_ref(), PAGE_SIZE).await
+        })
+    }
+
+    /// The smallest set of folders which have to be rescanned to pick up all changed paths.
+    ///
+    /// Every changed path contributes its parent folder, so moved items contribute
+    /// both their old and their new parent.
+    /// Once `siblings` or more folders with the same parent have to be rescanned,
+    /// they are collapsed into their parent instead.
+    /// With `siblings` of 0 or 1, any change therefore collapses into `/`,
+    /// which rescans the whole drive.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn scan_folders(&self, siblings: usize) -> Result<Vec<PathBuf>> {
+        let paths = self.paths().await?;
+
+        let folders = paths
+            .into_iter()
+            .map(|path| InnerPath::from(path).path)
+            .filter_map(|path| path.parent().map(PathBuf::from));
+
+        Ok(minimal_folders(folders, siblings))
+    }
+}
+
+/// The changes a synchronisation would make, without them being committed.
+#[derive(Debug)]
+pub struct Preview {
+    pub paths: Vec<ChangedPath>,
+    pub files: Vec<ChangedFile>,
+    pub folders: Vec<ChangedFolder>,
+}
+
+/// Turn a function fetching the page after a key into a stream of items.
+fn paginate<'a, T, F, Fut>(
+    key: fn(&T) -> ChangelogKey,
+    mut page: F,
+) -> impl Stream<Item = Result<T>> + 'a
+where
+    T: 'a,
+    F: FnMut(Option<ChangelogKey>) -> Fut + 'a,
+    Fut: Future<Output = Result<Vec<T>>> + 'a,
+{
+    // The state is `None` once the last page has been fetched.
+    stream::try_unfold(Some(None), move |after| {
+        let page = after.map(&mut page);
+
+        async move {
+            let items = match page {
+                Some(page) => page.await?,
+                None => return Ok::<_, crate::Error>(None),
+            };
+
+            let next = match items.len() < PAGE_SIZE as usize {
+                true => None,
+                false => items.last().map(|item| Some(key(item))),
+            };
+
+            Ok(Some((stream::iter(items.into_iter().map(Ok)), next)))
+        }
+    })
+    .try_flatten()
+}
+
+fn minimal_folders<I>(folders: I, siblings: usize) -> Vec<PathBuf>
+where
+    I: IntoIterator<Item = PathBuf>,
+{
+    let mut folders: BTreeSet<PathBuf> = folders.into_iter().collect();
+
+    loop {
+        // Rescanning a folder also rescans all of its descendants.
+        let descendants: Vec<PathBuf> = folders
+            .iter()
+            .filter(|folder| folder.ancestors().skip(1).any(|a| folders.contains(a)))
+            .cloned()
+            .collect();
+
+        for folder in &descendants {
+            folders.remove(folder);
+        }
+
+        let mut children: BTreeMap<PathBuf, usize> = BTreeMap::new();
+        for parent in folders.iter().filter_map(|folder| folder.parent()) {
+            *children.entry(parent.to_owned()).or_default() += 1;
+        }
+
+        let collapsed: Vec<PathBuf> = children
+            .into_iter()
+            .filter(|(_, count)| *count >= siblings)
+            .map(|(parent, _)| parent)
+            .collect();
+
+        if collapsed.is_empty() {
+            return folders.into_iter().collect();
+        }
+
+        folders.extend(collapsed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::minimal_folders;
+    use std::path::PathBuf;
+
+    fn folders(paths: &[&str], siblings: usize) -> Vec<String> {
+        minimal_folders(paths.iter().map(PathBuf::from), siblings)
+            .into_iter()
+            .map(|folder| folder.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn descendants_are_dropped() {
+        let paths = ["/TV/Show/Season 1", "/TV/Show", "/Movies", "/Movies"];
+
+        assert_eq!(folders(&paths, 3), ["/Movies", "/TV/Show"]);
+    }
+
+    #[test]
+    fn siblings_collapse_into_their_parent() {
+        let paths = ["/TV/A", "/TV/B", "/TV/C/D", "/Movies/E"];
+
+        assert_eq!(
+            folders(&paths, 3),
+            ["/Movies/E", "/TV/A", "/TV/B", "/TV/C/D"]
+        );
+        assert_eq!(folders(&paths, 2), ["/Movies/E", "/TV"]);
+    }
+
+    #[test]
+    fn collapsed_folders_collapse_further() {
+        let paths = ["/TV/Show/Season 1", "/TV/Show/Season 2", "/TV/Other"];
+
+        assert_eq!(folders(&paths, 2), ["/TV"]);
+    }
+
+    #[test]
+    fn less_than_two_siblings_rescan_the_drive() {
+        let paths = ["/TV/Show/Season 1", "/Movies"];
+
+        assert_eq!(folders(&paths, 1), ["/"]);
+        assert_eq!(folders(&paths, 0), ["/"]);
+        assert!(folders(&[], 0).is_empty());
     }
 }
@@ -0,0 +1,218 @@
+use crate::database::{self, Pool};
+use crate::{Result, UnknownDrive};
+use futures::prelude::*;
+use snafu::OptionExt;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// The anomalies in the stored items of a Shared Drive,
+/// which the paths cannot be derived from.
+///
+/// The categories are disjoint: an item is only reported as `unreachable`
+/// if none of the other categories applies to the item itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    pub drive_id: String,
+    /// Folders of which the chain of parents loops back onto itself.
+    pub cycles: Vec<String>,
+    /// Items of which the parent is missing, as well as folders other than the root without a parent.
+    ///
+    /// The parent of a stored item always belongs to the same Shared Drive,
+    /// as the foreign key of the parent includes the drive.
+    pub orphans: Vec<String>,
+    /// Items within a cycle or an orphan.
+    pub unreachable: Vec<String>,
+    /// Created items in the changelog which are not stored as such,
+    /// left behind by a synchronisation which did not complete.
+    pub stale_changelog: Vec<String>,
+}
+
+impl IntegrityReport {
+    pub fn is_healthy(&self) -> bool {
+        self.cycles.is_empty()
+            && self.orphans.is_empty()
+            && self.unreachable.is_empty()
+            && self.stale_changelog.is_empty()
+    }
+
+    /// The items which cause the anomalies, hence have to be fetched again.
+    ///
+    /// Unreachable items are fine once the items above them are repaired.
+    pub(crate) fn causes(&self) -> impl Iterator<Item = &str> {
+        self.cycles.iter().chain(&self.orphans).map(String::as_str)
+    }
+}
+
+/// Where the chain of parents of a folder leads to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reach {
+    Root,
+    Cycle,
+    /// The folder itself has a missing parent.
+    Orphan,
+    Unreachable,
+}
+
+#[tracing::instrument(level = "debug", skip(pool))]
+pub(crate) async fn check(drive_id: &str, pool: &Pool) -> Result<IntegrityReport> {
+    database::get_drive(drive_id, pool)
+        .await?
+        .context(UnknownDrive { drive_id })?;
+
+    let mut conn = pool.acquire().await?;
+
+    let parents: HashMap<String, Option<String>> = sqlx::query!(
+        "SELECT id, parent FROM folders WHERE drive_id = $1",
+        drive_id
+    )
+    .fetch(&mut conn)
+    .map_ok(|row| (row.id, row.parent))
+    .try_collect()
+    .await?;
+
+    let reach = reach_folders(drive_id, &parents);
+
+    let mut report = IntegrityReport {
+        drive_id: drive_id.to_owned(),
+        ..Default::default()
+    };
+
+    for (id, reach) in &reach {
+        match reach {
+            Reach::Root => (),
+            Reach::Cycle => report.cycles.push(id.to_string()),
+            Reach::Orphan => report.orphans.push(id.to_string()),
+            Reach::Unreachable => report.unreachable.push(id.to_string()),
+        }
+    }
+
+    let mut files =
+        sqlx::query!("SELECT id, parent FROM files WHERE drive_id = $1", drive_id).fetch(&mut conn);
+
+    while let Some(file) = files.try_next().await? {
+        match reach.get(file.parent.as_str()) {
+            Some(Reach::Root) => (),
+            Some(_) => report.unreachable.push(file.id),
+            None => report.orphans.push(file.id),
+        }
+    }
+
+    drop(files);
+
+    // A created item of the changelog always matches the stored item,
+    // unless the item has been deleted since.
+    report.stale_changelog = sqlx::query!(
+        r#"
+        SELECT c.id FROM folder_changelog c
+        WHERE c.drive_id = $1 AND c.deleted = FALSE
+        AND NOT EXISTS (
+            SELECT 1 FROM folders f
+            WHERE f.id = c.id AND f.drive_id = c.drive_id
+            AND f.name = c.name AND f.trashed = c.trashed
+            AND (f.parent = c.parent OR (f.parent IS NULL AND c.parent IS NULL))
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM folder_changelog d
+            WHERE d.id = c.id AND d.drive_id = c.drive_id AND d.deleted = TRUE
+        )
+        "#,
+        drive_id
+    )
+    .fetch(&mut conn)
+    .map_ok(|row| row.id)
+    .try_collect()
+    .await?;
+
+    let stale_files: Vec<String> = sqlx::query!(
+        r#"
+        SELECT c.id FROM file_changelog c
+        WHERE c.drive_id = $1 AND c.deleted = FALSE
+        AND NOT EXISTS (
+            SELECT 1 FROM files f
+            WHERE f.id = c.id AND f.drive_id = c.drive_id
+            AND f.name = c.name AND f.trashed = c.trashed AND f.parent = c.parent
+            AND f.md5 = c.md5 AND f.size = c.size
+        )
+        AND NOT EXISTS (
+            SELECT 1 FROM file_changelog d
+            WHERE d.id = c.id AND d.drive_id = c.drive_id AND d.deleted = TRUE
+        )
+        "#,
+        drive_id
+    )
+    .fetch(&mut conn)
+    .map_ok(|row| row.id)
+    .try_collect()
+    .await?;
+
+    report.stale_changelog.extend(stale_files);
+
+    for ids in [
+        &mut report.cycles,
+        &mut report.orphans,
+        &mut report.unreachable,
+        &mut report.stale_changelog,
+    ] {
+        ids.sort();
+    }
+
+    match report.is_healthy() {
+        true => info!("drive is healthy"),
+        false => warn!(?report, "drive has anomalies"),
+    }
+
+    Ok(report)
+}
+
+/// Follow the parents of every folder without recursing,
+/// so a cycle cannot loop forever like the recursive queries would.
+fn reach_folders<'a>(
+    drive_id: &str,
+    parents: &'a HashMap<String, Option<String>>,
+) -> HashMap<&'a str, Reach> {
+    let mut reach: HashMap<&str, Reach> = HashMap::new();
+
+    for start in parents.keys() {
+        // The folders visited from `start` which do not have a reach yet, in order.
+        let mut chain: Vec<&str> = Vec::new();
+        let mut current = start.as_str();
+
+        let end = loop {
+            if let Some(&known) = reach.get(current) {
+                break match known {
+                    Reach::Root => Reach::Root,
+                    _ => Reach::Unreachable,
+                };
+            }
+
+            if let Some(position) = chain.iter().position(|&id| id == current) {
+                for id in chain.drain(position..) {
+                    reach.insert(id, Reach::Cycle);
+                }
+                break Reach::Unreachable;
+            }
+
+            chain.push(current);
+
+            match parents[current].as_deref() {
+                None if current == drive_id => {
+                    chain.pop();
+                    reach.insert(current, Reach::Root);
+                    break Reach::Root;
+                }
+                Some(parent) if parents.contains_key(parent) => current = parent,
+                _ => {
+                    chain.pop();
+                    reach.insert(current, Reach::Orphan);
+                    break Reach::Unreachable;
+                }
+            }
+        };
+
+        for id in chain {
+            reach.insert(id, end);
+        }
+    }
+
+    reach
+}
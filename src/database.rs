@@ -1,28 +1,443 @@
+use crate::changes::Preview;
 use crate::fetch::{Change, Item};
-use crate::model::{ChangedFile, ChangedFolder, ChangedPath, Drive, File, Folder};
-use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions};
+use crate::model::{
+    ChangedFile, ChangedFolder, ChangedPath, ChangelogKey, Drive, File, Folder, FolderStats, Path,
+    PendingChanges, Quarantined, SyncLock, SyncRun, Tombstone,
+};
+use crate::safeguard::DeletionSummary;
+use crate::store::quarantine::{self, Partition};
+use crate::store::{Changelog, Connect, Merge, MergeOptions, Store};
+use crate::SyncInProgress;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use futures::prelude::*;
+use std::collections::{HashMap, HashSet};
 use tracing::trace;
 
-pub(crate) type Connection = SqliteConnection;
+#[cfg(not(feature = "postgres"))]
+mod backend {
+    use super::{DatabaseOptions, JournalMode, Synchronous};
+    use sqlx::sqlite::{
+        Sqlite, SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePool,
+        SqlitePoolOptions, SqliteSynchronous,
+    };
+    use sqlx::Executor;
+
+    pub(crate) type Connection = SqliteConnection;
+
+    pub(crate) type Pool = SqlitePool;
+
+    pub(crate) type Transaction = sqlx::Transaction<'static, Sqlite>;
+
+    /// The error code of a violated foreign key constraint.
+    pub(crate) const FOREIGN_KEY_VIOLATION: &str = "787";
+
+    /// The database path of a database which only lives as long as its connection.
+    const IN_MEMORY: &str = ":memory:";
+
+    /// The busy timeout of SQLite, unless it is configured.
+    const DEFAULT_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+    pub async fn establish_connection(options: &DatabaseOptions) -> sqlx::Result<Pool> {
+        let mut connect = SqliteConnectOptions::default()
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .filename(&options.database_path);
+
+        if let Some(mode) = options.journal_mode {
+            connect = connect.journal_mode(mode.into());
+        }
+
+        if let Some(synchronous) = options.synchronous {
+            connect = connect.synchronous(synchronous.into());
+        }
+
+        if let Some(timeout) = options.busy_timeout {
+            connect = connect.busy_timeout(timeout);
+        }
+
+        let mut pool = SqlitePoolOptions::new();
+
+        // Every connection to `:memory:` opens a database of its own,
+        // so the single connection must never be closed nor replaced.
+        // Waiting for the connection fails like waiting for a lock would, rather than forever,
+        // such as while `Changes` keep it for their read transaction.
+        if options.database_path == IN_MEMORY {
+            pool = pool
+                .max_connections(1)
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_timeout(options.busy_timeout.unwrap_or(DEFAULT_BUSY_TIMEOUT));
+        } else if let Some(max) = options.max_connections {
+            pool = pool.max_connections(max);
+        }
+
+        let cache_size = options.cache_size;
+        let mmap_size = options.mmap_size;
+
+        let pool = pool
+            .after_connect(move |conn| {
+                Box::pin(async move {
+                    // A negative cache size is in kibibytes rather than pages.
+                    if let Some(kibibytes) = cache_size {
+                        let pragma = format!("PRAGMA cache_size = -{}", kibibytes);
+                        conn.execute(pragma.as_str()).await?;
+                    }
+
+                    if let Some(bytes) = mmap_size {
+                        let pragma = format!("PRAGMA mmap_size = {}", bytes);
+                        conn.execute(pragma.as_str()).await?;
+                    }
+
+                    Ok(())
+                })
+            })
+            .connect_with(connect)
+            .await?;
+
+        sqlx::migrate!().run(&pool).await?;
+
+        Ok(pool)
+    }
+
+    /// Open an existing database without modifying it, hence without running the migrations.
+    pub async fn open_read_only(database_path: &str) -> sqlx::Result<Pool> {
+        let options = SqliteConnectOptions::default()
+            .read_only(true)
+            .filename(database_path);
+
+        SqlitePoolOptions::new().connect_with(options).await
+    }
+
+    impl From<JournalMode> for SqliteJournalMode {
+        fn from(mode: JournalMode) -> Self {
+            match mode {
+                JournalMode::Delete => Self::Delete,
+                JournalMode::Truncate => Self::Truncate,
+                JournalMode::Persist => Self::Persist,
+                JournalMode::Memory => Self::Memory,
+                JournalMode::Wal => Self::Wal,
+                JournalMode::Off => Self::Off,
+            }
+        }
+    }
+
+    impl From<Synchronous> for SqliteSynchronous {
+        fn from(synchronous: Synchronous) -> Self {
+            match synchronous {
+                Synchronous::Off => Self::Off,
+                Synchronous::Normal => Self::Normal,
+                Synchronous::Full => Self::Full,
+                Synchronous::Extra => Self::Extra,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod backend {
+    use super::DatabaseOptions;
+    use sqlx::postgres::{PgConnectOptions, PgConnection, PgPool, PgPoolOptions, Postgres};
+    use std::str::FromStr;
+
+    pub(crate) type Connection = PgConnection;
+
+    pub(crate) type Pool = PgPool;
+
+    pub(crate) type Transaction = sqlx::Transaction<'static, Postgres>;
+
+    /// The error code of a violated foreign key constraint.
+    pub(crate) const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+    pub async fn establish_connection(options: &DatabaseOptions) -> sqlx::Result<Pool> {
+        let connect = PgConnectOptions::from_str(&options.database_path)?;
+        let mut pool = PgPoolOptions::new();
+
+        if let Some(max) = options.max_connections {
+            pool = pool.max_connections(max);
+        }
+
+        let pool = pool.connect_with(connect).await?;
+
+        sqlx::migrate!("./migrations-postgres").run(&pool).await?;
+
+        Ok(pool)
+    }
+
+    /// Connect to an existing database in read-only mode, hence without running the migrations.
+    pub async fn open_read_only(database_url: &str) -> sqlx::Result<Pool> {
+        let options = PgConnectOptions::from_str(database_url)?;
+
+        PgPoolOptions::new()
+            .after_connect(|conn| {
+                Box::pin(async move {
+                    sqlx::query("SET default_transaction_read_only = on")
+                        .execute(conn)
+                        .await?;
+                    Ok(())
+                })
+            })
+            .connect_with(options)
+            .await
+    }
+}
+
+pub use backend::{establish_connection, open_read_only};
+pub(crate) use backend::{Connection, Pool, Transaction, FOREIGN_KEY_VIOLATION};
+
+/// Connects to the database at a path, or at a Postgres connection URL.
+///
+/// The SQLite path `:memory:` keeps the database in memory on a single connection,
+/// so it is gone once Bernard is closed.
+/// As `Changes` keep that connection until they are dropped,
+/// any other use of the database in the meantime fails once the busy timeout has passed.
+pub struct DatabaseOptions {
+    database_path: String,
+    pub(crate) max_connections: Option<u32>,
+    pub(crate) disambiguate_paths: bool,
+    #[cfg(not(feature = "postgres"))]
+    pub(crate) journal_mode: Option<JournalMode>,
+    #[cfg(not(feature = "postgres"))]
+    pub(crate) synchronous: Option<Synchronous>,
+    #[cfg(not(feature = "postgres"))]
+    pub(crate) busy_timeout: Option<std::time::Duration>,
+    #[cfg(not(feature = "postgres"))]
+    pub(crate) cache_size: Option<u64>,
+    #[cfg(not(feature = "postgres"))]
+    pub(crate) mmap_size: Option<u64>,
+}
+
+impl DatabaseOptions {
+    pub fn new<S: Into<String>>(database_path: S) -> Self {
+        Self {
+            database_path: database_path.into(),
+            max_connections: None,
+            disambiguate_paths: false,
+            #[cfg(not(feature = "postgres"))]
+            journal_mode: None,
+            #[cfg(not(feature = "postgres"))]
+            synchronous: None,
+            #[cfg(not(feature = "postgres"))]
+            busy_timeout: None,
+            #[cfg(not(feature = "postgres"))]
+            cache_size: None,
+            #[cfg(not(feature = "postgres"))]
+            mmap_size: None,
+        }
+    }
+}
+
+/// The SQLite `journal_mode`, see <https://www.sqlite.org/pragma.html#pragma_journal_mode>.
+#[cfg(not(feature = "postgres"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
 
-pub(crate) type Pool = SqlitePool;
+/// The SQLite `synchronous` level, see <https://www.sqlite.org/pragma.html#pragma_synchronous>.
+#[cfg(not(feature = "postgres"))]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
 
-pub async fn establish_connection(database_path: &str) -> sqlx::Result<Pool> {
-    let options = SqliteConnectOptions::default()
-        .create_if_missing(true)
-        .foreign_keys(true)
-        .filename(database_path);
+#[async_trait]
+impl Connect for DatabaseOptions {
+    type Store = DatabaseStore;
 
-    let pool = SqlitePoolOptions::new().connect_with(options).await?;
+    async fn connect(self) -> crate::Result<DatabaseStore> {
+        let pool = establish_connection(&self).await?;
 
-    sqlx::migrate!().run(&pool).await?;
+        Ok(DatabaseStore {
+            pool,
+            disambiguate_paths: self.disambiguate_paths,
+        })
+    }
+}
 
-    Ok(pool)
+/// The default store, which keeps the metadata in SQLite or, with the `postgres` feature, in Postgres.
+pub struct DatabaseStore {
+    pub(crate) pool: Pool,
+    pub(crate) disambiguate_paths: bool,
+}
+
+#[async_trait]
+impl Store for DatabaseStore {
+    type Changelog = DatabaseChangelog;
+
+    async fn get_drive(&self, drive_id: &str) -> crate::Result<Option<Drive>> {
+        Ok(get_drive(drive_id, &self.pool).await?)
+    }
+
+    async fn add_drive(
+        &self,
+        drive_id: &str,
+        name: &str,
+        page_token: &str,
+        items: Vec<Item>,
+    ) -> crate::Result<()> {
+        let disambiguate = self.disambiguate_paths;
+
+        Ok(add_drive(drive_id, name, page_token, items, disambiguate, &self.pool).await?)
+    }
+
+    async fn merge_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        page_token: &str,
+        options: &MergeOptions<'_>,
+    ) -> crate::Result<Merge> {
+        let disambiguate = self.disambiguate_paths;
+
+        merge_changes(
+            drive_id,
+            changes,
+            page_token,
+            options,
+            disambiguate,
+            &self.pool,
+        )
+        .await
+    }
+
+    async fn remove_drive(&self, drive_id: &str, emit_changes: bool) -> crate::Result<()> {
+        Ok(remove_drive(drive_id, emit_changes, &self.pool).await?)
+    }
+
+    async fn preview_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        quarantine: bool,
+    ) -> crate::Result<Preview> {
+        let disambiguate = self.disambiguate_paths;
+
+        Ok(preview_changes(drive_id, changes, quarantine, disambiguate, &self.pool).await?)
+    }
+
+    async fn clear_changelog(&self, drive_id: &str) -> crate::Result<()> {
+        Ok(clear_changelog(drive_id, &self.pool).await?)
+    }
+
+    async fn save_pending_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        page_token: &str,
+    ) -> crate::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        PendingChanges::replace(drive_id, changes, page_token, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn pending_changes(&self, drive_id: &str) -> crate::Result<Option<PendingChanges>> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(PendingChanges::get(drive_id, &mut conn).await?)
+    }
+
+    async fn clear_pending_changes(&self, drive_id: &str) -> crate::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        PendingChanges::clear(drive_id, &mut tx).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn changelog(&self, drive_id: &str) -> crate::Result<DatabaseChangelog> {
+        let tx = snapshot(drive_id, &self.pool).await?;
+
+        Ok(DatabaseChangelog {
+            drive_id: drive_id.to_owned(),
+            tx,
+        })
+    }
+
+    async fn acquire_lease(
+        &self,
+        drive_id: &str,
+        holder: &str,
+        lease: Duration,
+    ) -> crate::Result<bool> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(SyncLock::acquire(drive_id, holder, lease, &mut conn).await?)
+    }
+
+    async fn release_lease(&self, drive_id: &str, holder: &str) -> crate::Result<()> {
+        let mut conn = self.pool.acquire().await?;
+
+        Ok(SyncLock::release(drive_id, holder, &mut conn).await?)
+    }
+
+    async fn close(&self) {
+        self.pool.close().await
+    }
+}
+
+/// The changelog as seen by a read transaction.
+pub struct DatabaseChangelog {
+    drive_id: String,
+    tx: Transaction,
+}
+
+#[async_trait]
+impl Changelog for DatabaseChangelog {
+    async fn get_changed_files(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> crate::Result<Vec<ChangedFile>> {
+        Ok(get_changed_files(&self.drive_id, after, limit, &mut self.tx).await?)
+    }
+
+    async fn get_changed_folders(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> crate::Result<Vec<ChangedFolder>> {
+        Ok(get_changed_folders(&self.drive_id, after, limit, &mut self.tx).await?)
+    }
+
+    async fn get_changed_paths(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> crate::Result<Vec<ChangedPath>> {
+        Ok(get_changed_paths(&self.drive_id, after, limit, &mut self.tx).await?)
+    }
 }
 
 pub async fn clear_changelog(drive_id: &str, pool: &Pool) -> sqlx::Result<()> {
-    ChangedFolder::clear(drive_id, pool).await?;
-    ChangedFile::clear(drive_id, pool).await?;
+    let mut conn = pool.acquire().await?;
+    clear_changelog_inner(drive_id, &mut conn).await
+}
+
+async fn clear_changelog_inner(drive_id: &str, conn: &mut Connection) -> sqlx::Result<()> {
+    ChangedFolder::clear(drive_id, conn).await?;
+    ChangedFile::clear(drive_id, conn).await?;
+    ChangedPath::clear(drive_id, conn).await?;
+
+    Ok(())
+}
+
+/// The changelog of a removed drive is kept for its `Changes`, until the next removal or maintenance.
+pub(crate) async fn clear_removed_changelogs(conn: &mut Connection) -> sqlx::Result<()> {
+    ChangedFolder::clear_removed_drives(conn).await?;
+    ChangedFile::clear_removed_drives(conn).await?;
+    ChangedPath::clear_removed_drives(conn).await?;
 
     Ok(())
 }
@@ -38,52 +453,165 @@ async fn delete_file_or_folder(
     Ok(())
 }
 
-fn item_to_change(drive_id: &str, item: Item) -> Change {
-    match item.drive_id() == drive_id {
-        true => Change::ItemChanged(item),
-        false => {
-            trace!("moved to another shared drive, marked as removed");
-            Change::ItemRemoved(item.into_id())
+#[tracing::instrument(level = "debug", skip(changes, options, pool))]
+pub async fn merge_changes(
+    drive_id: &str,
+    changes: &[Change],
+    page_token: &str,
+    options: &MergeOptions<'_>,
+    disambiguate: bool,
+    pool: &Pool,
+) -> crate::Result<Merge> {
+    let mut tx = pool.begin().await?;
+
+    // Holding the lease also locks it until the merge commits, so no other holder can take it over.
+    if let Some(holder) = options.holder {
+        if !SyncLock::hold(drive_id, holder, &mut tx).await? {
+            tx.rollback().await?;
+            return Err(SyncInProgress { drive_id }.build().into());
+        }
+    }
+
+    clear_changelog_inner(drive_id, &mut tx).await?;
+
+    // First update the page_token
+    Drive::update_page_token(drive_id, page_token, &mut tx).await?;
+
+    let files = match options.safeguard {
+        Some(_) => File::count(drive_id, &mut tx).await?,
+        None => 0,
+    };
+
+    match options.quarantine {
+        true => apply_or_quarantine(drive_id, changes, &mut tx).await?,
+        false => apply_changes(drive_id, changes, &mut tx).await?,
+    }
+
+    // Tombstones take the last known path, so record them before the paths are refreshed.
+    let now = Utc::now();
+    Tombstone::record(drive_id, now, &mut tx).await?;
+
+    if let Some(retention) = options.retention {
+        Tombstone::prune(drive_id, now - retention, &mut tx).await?;
+    }
+
+    refresh_paths(drive_id, disambiguate, &mut tx).await?;
+    FolderStats::refresh(drive_id, &mut tx).await?;
+    SyncRun::record(drive_id, page_token, now, &mut tx).await?;
+
+    if let Some(safeguard) = options.safeguard {
+        let (deleted, trashed) = ChangedFile::count_removed(drive_id, &mut tx).await?;
+
+        let summary = DeletionSummary {
+            drive_id: drive_id.to_owned(),
+            files: files as u64,
+            deleted: deleted as u64,
+            trashed: trashed as u64,
+        };
+
+        if safeguard.is_exceeded(&summary) {
+            tx.rollback().await?;
+            return Ok(Merge::Rejected(summary));
         }
     }
+
+    tx.commit().await?;
+    Ok(Merge::Committed)
 }
 
+/// Apply the changes within a transaction and collect the resulting changelog,
+/// after which the transaction is rolled back.
 #[tracing::instrument(level = "debug", skip(changes, pool))]
-pub async fn merge_changes<I>(
+pub async fn preview_changes(
     drive_id: &str,
-    changes: I,
-    page_token: &str,
+    changes: &[Change],
+    quarantine: bool,
+    disambiguate: bool,
     pool: &Pool,
-) -> sqlx::Result<()>
-where
-    I: IntoIterator<Item = Change>,
-{
+) -> sqlx::Result<Preview> {
     let mut tx = pool.begin().await?;
 
-    // First update the page_token
-    Drive::update_page_token(drive_id, page_token, &mut tx).await?;
+    // Only the changelog of this preview should be visible.
+    clear_changelog_inner(drive_id, &mut tx).await?;
 
-    // If an item changes to another drive_id, consider it removed.
-    let changes = changes.into_iter().map(|change| match change {
-        Change::ItemChanged(item) => item_to_change(drive_id, item),
-        _ => change,
-    });
+    match quarantine {
+        true => apply_or_quarantine(drive_id, changes, &mut tx).await?,
+        false => apply_changes(drive_id, changes, &mut tx).await?,
+    }
 
+    // The paths of the changelog are only known once the paths are refreshed.
+    refresh_paths(drive_id, disambiguate, &mut tx).await?;
+
+    let preview = Preview {
+        paths: ChangedPath::get_all(drive_id, &mut tx).await?,
+        files: ChangedFile::get_all(drive_id, &mut tx).await?,
+        folders: ChangedFolder::get_all(drive_id, &mut tx).await?,
+    };
+
+    // Explicitly rollback, the page_token and the previous changelog remain untouched.
+    tx.rollback().await?;
+
+    Ok(preview)
+}
+
+/// Apply the changes of which the parents are known, and quarantine the others until they are.
+async fn apply_or_quarantine(
+    drive_id: &str,
+    changes: &[Change],
+    conn: &mut Connection,
+) -> sqlx::Result<()> {
+    let previous = Quarantined::get_all(drive_id, conn).await?;
+
+    let since: HashMap<String, DateTime<Utc>> = previous
+        .iter()
+        .map(|q| (q.item.id().to_owned(), q.quarantined_at))
+        .collect();
+    let retried: Vec<Change> = previous
+        .into_iter()
+        .map(|q| Change::ItemChanged(q.item))
+        .collect();
+
+    let changes = quarantine::retry(&retried, changes);
+    let mut existing = HashSet::new();
+
+    for parent in quarantine::parents(&changes) {
+        if Folder::exists(parent, drive_id, conn).await? {
+            existing.insert(parent);
+        }
+    }
+
+    let Partition { apply, quarantined } =
+        quarantine::partition(drive_id, changes, &existing, &since);
+
+    apply_changes(drive_id, apply, conn).await?;
+    Quarantined::replace_all(drive_id, &quarantined, conn).await
+}
+
+async fn apply_changes<'c, I>(drive_id: &str, changes: I, conn: &mut Connection) -> sqlx::Result<()>
+where
+    I: IntoIterator<Item = &'c Change>,
+{
     for change in changes {
         match change {
             Change::DriveChanged(drive) => {
-                Folder::update_name(drive_id, drive_id, &drive.name, &mut tx).await?
+                // The root folder of a Shared Drive shares its id with the drive.
+                Folder::update_name(&drive.id, drive_id, &drive.name, conn).await?
+            }
+            // If an item changes to another drive_id, consider it removed.
+            Change::ItemChanged(item) if item.drive_id() != drive_id => {
+                trace!("moved to another shared drive, marked as removed");
+                delete_file_or_folder(item.id(), drive_id, conn).await?
             }
             Change::ItemChanged(item) => match item {
-                Item::File(file) => file.upsert(&mut tx).await?,
-                Item::Folder(folder) => folder.upsert(&mut tx).await?,
+                Item::File(file) => file.upsert(conn).await?,
+                Item::Folder(folder) => folder.upsert(conn).await?,
             },
-            Change::ItemRemoved(id) => delete_file_or_folder(&id, drive_id, &mut tx).await?,
-            Change::DriveRemoved(_) => (),
+            Change::ItemRemoved(id) => delete_file_or_folder(id, drive_id, conn).await?,
+            Change::DriveRemoved(id) => trace!(%id, "ignoring removed drive"),
         }
     }
 
-    tx.commit().await
+    Ok(())
 }
 
 #[tracing::instrument(level = "debug", skip(name, items, pool))]
@@ -92,6 +620,7 @@ pub async fn add_drive<I>(
     name: &str,
     page_token: &str,
     items: I,
+    disambiguate: bool,
     pool: &Pool,
 ) -> sqlx::Result<()>
 where
@@ -99,8 +628,82 @@ where
 {
     let mut tx = pool.begin().await?;
 
+    // The changelog of a removed drive with the same id may still be around.
+    clear_changelog_inner(drive_id, &mut tx).await?;
+    create_drive(drive_id, name, page_token, items, disambiguate, &mut tx).await?;
+
+    // Explicitly commit (otherwise this would rollback on drop)
+    tx.commit().await
+}
+
+/// Add a Shared Drive from a snapshot without leaving a changelog behind,
+/// so the next synchronisation only reports the changes since the snapshot.
+///
+/// The items, which include the root folder, are inserted in batches as they are read.
+#[tracing::instrument(level = "debug", skip(items, pool))]
+pub async fn import_drive<S, E>(
+    drive_id: &str,
+    page_token: &str,
+    items: S,
+    disambiguate: bool,
+    pool: &Pool,
+) -> Result<(), E>
+where
+    S: Stream<Item = Result<Item, E>>,
+    E: From<sqlx::Error>,
+{
+    futures::pin_mut!(items);
+
+    let mut tx = pool.begin().await?;
     Drive::create(drive_id, page_token, &mut tx).await?;
 
+    let mut files = Vec::with_capacity(IMPORT_BATCH_SIZE);
+    let mut folders = Vec::with_capacity(IMPORT_BATCH_SIZE);
+
+    while let Some(item) = items.try_next().await? {
+        match item {
+            Item::File(file) => files.push(file),
+            Item::Folder(folder) => folders.push(folder),
+        }
+
+        if files.len() == IMPORT_BATCH_SIZE {
+            File::create_many(&files, &mut tx).await?;
+            files.clear();
+        }
+
+        if folders.len() == IMPORT_BATCH_SIZE {
+            Folder::create_many(&folders, &mut tx).await?;
+            folders.clear();
+        }
+    }
+
+    File::create_many(&files, &mut tx).await?;
+    Folder::create_many(&folders, &mut tx).await?;
+
+    index_drive(drive_id, page_token, disambiguate, &mut tx).await?;
+    clear_changelog_inner(drive_id, &mut tx).await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// The number of files or folders per INSERT of an import,
+/// which stays well below the number of parameters SQLite allows per statement.
+const IMPORT_BATCH_SIZE: usize = 500;
+
+async fn create_drive<I>(
+    drive_id: &str,
+    name: &str,
+    page_token: &str,
+    items: I,
+    disambiguate: bool,
+    conn: &mut Connection,
+) -> sqlx::Result<()>
+where
+    I: IntoIterator<Item = Item>,
+{
+    Drive::create(drive_id, page_token, conn).await?;
+
     let drive_folder = Folder {
         id: drive_id.to_owned(),
         drive_id: drive_id.to_owned(),
@@ -109,31 +712,119 @@ where
         trashed: false,
     };
 
-    drive_folder.create(&mut tx).await?;
+    drive_folder.create(conn).await?;
 
     for item in items {
         match item {
-            Item::File(file) => file.create(&mut tx).await?,
-            Item::Folder(folder) => folder.create(&mut tx).await?,
+            Item::File(file) => file.create(conn).await?,
+            Item::Folder(folder) => folder.create(conn).await?,
         }
     }
 
-    // Explicitly commit (otherwise this would rollback on drop)
+    index_drive(drive_id, page_token, disambiguate, conn).await
+}
+
+/// Derive the paths and folder statistics of a newly created drive and record its first sync run.
+async fn index_drive(
+    drive_id: &str,
+    page_token: &str,
+    disambiguate: bool,
+    conn: &mut Connection,
+) -> sqlx::Result<()> {
+    refresh_paths(drive_id, disambiguate, conn).await?;
+    FolderStats::refresh(drive_id, conn).await?;
+    SyncRun::record(drive_id, page_token, Utc::now(), conn).await?;
+
+    Ok(())
+}
+
+/// Refresh the materialised paths and record the paths of the changelog.
+///
+/// Switching the disambiguation on or off rebuilds the paths of the entire drive.
+async fn refresh_paths(
+    drive_id: &str,
+    disambiguate: bool,
+    conn: &mut Connection,
+) -> sqlx::Result<()> {
+    let rebuild = Drive::set_disambiguate_paths(drive_id, disambiguate, conn).await?;
+    Path::refresh(drive_id, disambiguate, rebuild, conn).await?;
+    ChangedPath::record(drive_id, conn).await
+}
+
+/// Remove a Shared Drive and everything within it.
+///
+/// The changelog only holds the deletions of the removal if they are emitted,
+/// otherwise it is empty.
+#[tracing::instrument(level = "debug", skip(pool))]
+pub async fn remove_drive(drive_id: &str, emit_changes: bool, pool: &Pool) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+
+    clear_removed_changelogs(&mut tx).await?;
+    clear_changelog_inner(drive_id, &mut tx).await?;
+
+    if emit_changes {
+        ChangedPath::record_removed(drive_id, &mut tx).await?;
+    }
+
+    Drive::delete(drive_id, &mut tx).await?;
+
+    if !emit_changes {
+        clear_changelog_inner(drive_id, &mut tx).await?;
+    }
+
     tx.commit().await
 }
 
+/// Begin a read transaction which observes the current state of the database
+/// until it is dropped.
+pub async fn snapshot(drive_id: &str, pool: &Pool) -> sqlx::Result<Transaction> {
+    let mut tx = pool.begin().await?;
+
+    // Postgres defaults to READ COMMITTED, where every statement observes the latest commit.
+    #[cfg(feature = "postgres")]
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+        .execute(&mut tx)
+        .await?;
+
+    // SQLite only starts the read transaction with the first SELECT statement.
+    Drive::get_by_id(drive_id, &mut tx).await?;
+
+    Ok(tx)
+}
+
 pub async fn get_drive(drive_id: &str, pool: &Pool) -> sqlx::Result<Option<Drive>> {
-    Drive::get_by_id(drive_id, pool).await
+    let mut conn = pool.acquire().await?;
+    Drive::get_by_id(drive_id, &mut conn).await
 }
 
-pub async fn get_changed_files(drive_id: &str, pool: &Pool) -> sqlx::Result<Vec<ChangedFile>> {
-    ChangedFile::get_all(drive_id, pool).await
+pub async fn get_drives(pool: &Pool) -> sqlx::Result<Vec<Drive>> {
+    let mut conn = pool.acquire().await?;
+    Drive::get_all(&mut conn).await
 }
 
-pub async fn get_changed_folders(drive_id: &str, pool: &Pool) -> sqlx::Result<Vec<ChangedFolder>> {
-    ChangedFolder::get_all(drive_id, pool).await
+pub async fn get_changed_files(
+    drive_id: &str,
+    after: Option<&ChangelogKey>,
+    limit: i64,
+    conn: &mut Connection,
+) -> sqlx::Result<Vec<ChangedFile>> {
+    ChangedFile::get_page(drive_id, after, limit, conn).await
 }
 
-pub async fn get_changed_paths(drive_id: &str, pool: &Pool) -> sqlx::Result<Vec<ChangedPath>> {
-    ChangedPath::get_all(drive_id, pool).await
+pub async fn get_changed_folders(
+    drive_id: &str,
+    after: Option<&ChangelogKey>,
+    limit: i64,
+    conn: &mut Connection,
+) -> sqlx::Result<Vec<ChangedFolder>> {
+    ChangedFolder::get_page(drive_id, after, limit, conn).await
+}
+
+pub async fn get_changed_paths(
+    drive_id: &str,
+    after: Option<&ChangelogKey>,
+    limit: i64,
+    conn: &mut Connection,
+) -> sqlx::Result<Vec<ChangedPath>> {
+    ChangedPath::get_page(drive_id, after, limit, conn).await
 }
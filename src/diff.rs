@@ -0,0 +1,224 @@
+use crate::database::{self, Pool};
+use crate::export::ExportFormat;
+use crate::import::Records;
+use crate::model::Record;
+use crate::{DifferentDrives, InvalidImport, Result, UnknownDrive};
+use futures::prelude::*;
+use snafu::OptionExt;
+use std::collections::HashMap;
+use tokio::io::AsyncRead;
+
+/// The files and folders of a Shared Drive at one point in time,
+/// read from a database or an export.
+#[derive(Debug)]
+pub struct Snapshot {
+    drive_id: String,
+    records: Vec<Record>,
+}
+
+/// A difference between two snapshots of the same Shared Drive.
+#[derive(Debug)]
+pub enum Difference {
+    Created(Record),
+    Deleted(Record),
+    /// The path changed, which might coincide with a modification.
+    ///
+    /// The descendants of a moved folder are only reported if they changed otherwise.
+    Moved {
+        old: Record,
+        new: Record,
+    },
+    /// The md5, size or trashed flag changed,
+    /// while the path remained the same or followed a moved folder.
+    Modified {
+        old: Record,
+        new: Record,
+    },
+}
+
+impl Difference {
+    /// The path of the item in the newer snapshot, or in the older one if it was deleted.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Created(record) | Self::Deleted(record) => record.path.as_deref(),
+            Self::Moved { new, .. } | Self::Modified { new, .. } => new.path.as_deref(),
+        }
+    }
+}
+
+impl Snapshot {
+    /// Read a Shared Drive from a Bernard database, which is opened read-only.
+    ///
+    /// The database must have been migrated by this version of Bernard,
+    /// and fails with `UnknownDrive` if it does not hold the Shared Drive.
+    pub async fn from_database(database_path: &str, drive_id: &str) -> Result<Self> {
+        let pool = database::open_read_only(database_path).await?;
+        let records = Self::read_database(drive_id, &pool).await;
+
+        pool.close().await;
+
+        Ok(Self {
+            drive_id: drive_id.to_owned(),
+            records: records?,
+        })
+    }
+
+    async fn read_database(drive_id: &str, pool: &Pool) -> Result<Vec<Record>> {
+        database::get_drive(drive_id, pool)
+            .await?
+            .context(UnknownDrive { drive_id })?;
+
+        let mut conn = pool.acquire().await?;
+        let records = Record::stream(drive_id, &mut conn).try_collect().await?;
+
+        Ok(records)
+    }
+
+    /// Read a Shared Drive from an export, whose records must all belong to the same drive.
+    pub async fn from_export<R>(format: ExportFormat, reader: R) -> Result<Self>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut records: Vec<Record> = Vec::new();
+        let mut reader = Records::new(format, reader);
+
+        while let Some(record) = reader.next().await? {
+            if let Some(first) = records.first() {
+                if record.drive_id != first.drive_id {
+                    return Err(InvalidImport {
+                        reason: format!(
+                            "{} belongs to Shared Drive {}",
+                            record.id, record.drive_id
+                        ),
+                    }
+                    .build()
+                    .into());
+                }
+            }
+
+            records.push(record);
+        }
+
+        let drive_id = match records.first() {
+            Some(record) => record.drive_id.clone(),
+            None => {
+                return Err(InvalidImport {
+                    reason: "the export is empty",
+                }
+                .build()
+                .into())
+            }
+        };
+
+        Ok(Self { drive_id, records })
+    }
+
+    pub fn drive_id(&self) -> &str {
+        &self.drive_id
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// The differences from this snapshot to a newer one of the same Shared Drive, ordered by path.
+    pub fn diff(&self, newer: &Snapshot) -> Result<Vec<Difference>> {
+        if self.drive_id != newer.drive_id {
+            return Err(DifferentDrives {
+                old: &self.drive_id,
+                new: &newer.drive_id,
+            }
+            .build()
+            .into());
+        }
+
+        let old: HashMap<_, _> = self
+            .records
+            .iter()
+            .map(|record| ((&record.drive_id, &record.id), record))
+            .collect();
+
+        let new: HashMap<_, _> = newer
+            .records
+            .iter()
+            .map(|record| ((&record.drive_id, &record.id), record))
+            .collect();
+
+        let differences: Vec<_> = newer
+            .records
+            .iter()
+            .filter_map(|record| match old.get(&(&record.drive_id, &record.id)) {
+                None => Some(Difference::Created(record.clone())),
+                Some(old) => compare(old, record),
+            })
+            .chain(
+                self.records
+                    .iter()
+                    .filter(|record| !new.contains_key(&(&record.drive_id, &record.id)))
+                    .map(|record| Difference::Deleted(record.clone())),
+            )
+            .collect();
+
+        // The old and new paths of the moved folders.
+        let moved: HashMap<_, _> = differences
+            .iter()
+            .filter_map(|difference| match difference {
+                Difference::Moved { old, new } if new.folder => {
+                    Some((old.path.clone()?, new.path.clone()?))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let mut differences: Vec<_> = differences
+            .into_iter()
+            .filter_map(|difference| match difference {
+                Difference::Moved { old, new } if follows_moved_folder(&old, &new, &moved) => {
+                    match modified(&old, &new) {
+                        true => Some(Difference::Modified { old, new }),
+                        false => None,
+                    }
+                }
+                difference => Some(difference),
+            })
+            .collect();
+
+        differences.sort_by(|a, b| a.path().cmp(&b.path()));
+        Ok(differences)
+    }
+}
+
+fn modified(old: &Record, new: &Record) -> bool {
+    old.md5 != new.md5 || old.size != new.size || old.trashed != new.trashed
+}
+
+fn compare(old: &Record, new: &Record) -> Option<Difference> {
+    let modified = modified(old, new);
+    let (old, new) = (old.clone(), new.clone());
+
+    match (old.path != new.path, modified) {
+        (true, _) => Some(Difference::Moved { old, new }),
+        (false, true) => Some(Difference::Modified { old, new }),
+        (false, false) => None,
+    }
+}
+
+/// Whether the path only changed because its closest moved ancestor did.
+fn follows_moved_folder(old: &Record, new: &Record, moved: &HashMap<String, String>) -> bool {
+    let (old, new) = match (&old.path, &new.path) {
+        (Some(old), Some(new)) => (old, new),
+        _ => return false,
+    };
+
+    let mut ancestor = old.as_str();
+
+    while let Some((parent, _)) = ancestor.rsplit_once('/') {
+        if let Some(new_parent) = moved.get(parent) {
+            return new.strip_prefix(new_parent.as_str()) == old.strip_prefix(parent);
+        }
+
+        ancestor = parent;
+    }
+
+    false
+}
@@ -0,0 +1,107 @@
+use crate::database::{self, Pool};
+use crate::model::Record;
+use crate::{Result, UnknownDrive, WriteExport};
+use futures::prelude::*;
+use snafu::{OptionExt, ResultExt};
+use std::io;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+
+/// The formats a Shared Drive can be exported to and imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A JSON object per line.
+    Ndjson,
+    /// Comma-separated values with a header row.
+    Csv,
+    /// An indented JSON array.
+    Json,
+}
+
+pub(crate) const CSV_HEADER: &str = "id,drive_id,parent,name,path,folder,trashed,md5,size\n";
+
+#[tracing::instrument(level = "debug", skip(writer, pool))]
+pub(crate) async fn export<W>(
+    drive_id: &str,
+    format: ExportFormat,
+    writer: W,
+    pool: &Pool,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    database::get_drive(drive_id, pool)
+        .await?
+        .context(UnknownDrive { drive_id })?;
+
+    let mut conn = pool.acquire().await?;
+    let mut records = Record::stream(drive_id, &mut conn);
+    let mut writer = BufWriter::new(writer);
+
+    match format {
+        ExportFormat::Csv => write(&mut writer, CSV_HEADER).await?,
+        ExportFormat::Json => write(&mut writer, "[").await?,
+        ExportFormat::Ndjson => (),
+    }
+
+    let mut first = true;
+
+    while let Some(record) = records.try_next().await? {
+        let line = match format {
+            ExportFormat::Ndjson => to_json(&record, false)? + "\n",
+            ExportFormat::Csv => to_csv(&record),
+            // Indent the objects within the array.
+            ExportFormat::Json => {
+                let object = to_json(&record, true)?.replace('\n', "\n  ");
+                format!("{}\n  {}", if first { "" } else { "," }, object)
+            }
+        };
+
+        write(&mut writer, &line).await?;
+        first = false;
+    }
+
+    if format == ExportFormat::Json {
+        write(&mut writer, if first { "]\n" } else { "\n]\n" }).await?;
+    }
+
+    writer.flush().await.context(WriteExport)?;
+    Ok(())
+}
+
+async fn write<W: AsyncWrite + Unpin>(writer: &mut W, s: &str) -> Result<()> {
+    writer.write_all(s.as_bytes()).await.context(WriteExport)?;
+    Ok(())
+}
+
+fn to_json(record: &Record, pretty: bool) -> Result<String> {
+    let json = match pretty {
+        true => serde_json::to_string_pretty(record),
+        false => serde_json::to_string(record),
+    };
+
+    Ok(json.map_err(io::Error::from).context(WriteExport)?)
+}
+
+fn to_csv(record: &Record) -> String {
+    let fields = [
+        csv_field(&record.id),
+        csv_field(&record.drive_id),
+        csv_field(record.parent.as_deref().unwrap_or_default()),
+        csv_field(&record.name),
+        csv_field(record.path.as_deref().unwrap_or_default()),
+        record.folder.to_string(),
+        record.trashed.to_string(),
+        csv_field(record.md5.as_deref().unwrap_or_default()),
+        record.size.map(|size| size.to_string()).unwrap_or_default(),
+    ];
+
+    fields.join(",") + "\n"
+}
+
+/// Quote fields containing separators, quotes or newlines, as per RFC 4180.
+fn csv_field(field: &str) -> String {
+    match field.contains(&[',', '"', '\n', '\r'][..]) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_owned(),
+    }
+}
@@ -1,4 +1,4 @@
-use super::{Fetcher, Item, Result};
+use super::{Error, Fetcher, Item, Result};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -60,4 +60,30 @@ impl Fetcher {
             }
         }
     }
+
+    /// Returns `None` if the item no longer exists, or the Service Account lost access to it.
+    pub async fn item(self: Arc<Fetcher>, id: &str) -> Result<Option<Item>> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Query<'a> {
+            fields: &'a str,
+            supports_all_drives: bool,
+        }
+
+        let query = Query {
+            fields: "id,driveId,name,parents,md5Checksum,size,trashed",
+            supports_all_drives: true,
+        };
+
+        let request = self
+            .client
+            .get(format!("https://www.googleapis.com/drive/v3/files/{}", id))
+            .query(&query);
+
+        match self.with_retry(request).await {
+            Ok(item) => Ok(Some(item)),
+            Err(Error::DriveNotFound { .. }) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
 }
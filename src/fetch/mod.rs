@@ -174,7 +174,7 @@ impl FetchBuilder {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Item {
     File(File),
     Folder(Folder),
@@ -188,10 +188,10 @@ impl Item {
         }
     }
 
-    pub fn into_id(self) -> String {
+    pub fn id(&self) -> &'_ str {
         match self {
-            Item::File(file) => file.id,
-            Item::Folder(folder) => folder.id,
+            Item::File(file) => &file.id,
+            Item::Folder(folder) => &folder.id,
         }
     }
 }
@@ -248,7 +248,7 @@ impl<'de> Deserialize<'de> for Item {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Change {
     DriveChanged(PartialDrive),
     DriveRemoved(String),
@@ -256,7 +256,7 @@ pub enum Change {
     ItemRemoved(String),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct PartialDrive {
     pub id: String,
     pub name: String,
@@ -0,0 +1,92 @@
+/// A glob pattern for paths such as `/TV/**/*.mkv`.
+///
+/// `*` and `?` match within a single segment of the path,
+/// whereas `**` matches any number of segments.
+#[derive(Debug)]
+pub(crate) struct Glob {
+    segments: Vec<String>,
+}
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Self {
+        let segments = pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(ToOwned::to_owned)
+            .collect();
+
+        Self { segments }
+    }
+
+    /// The segments up to the first wildcard, which every matching path starts with.
+    pub(crate) fn prefix(&self) -> String {
+        self.segments
+            .iter()
+            .take_while(|segment| !has_wildcard(segment))
+            .flat_map(|segment| vec!["/", segment])
+            .collect()
+    }
+
+    pub(crate) fn is_literal(&self) -> bool {
+        !self.segments.iter().any(|segment| has_wildcard(segment))
+    }
+
+    pub(crate) fn is_match(&self, path: &str) -> bool {
+        let path: Vec<_> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match_segments(&self.segments, &path)
+    }
+}
+
+fn has_wildcard(segment: &str) -> bool {
+    segment.contains(&['*', '?'][..])
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((segment, rest)) if segment == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path)) => match_segment(segment, name) && match_segments(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` in the pattern and the name position it was tried at.
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            // Let the last `*` consume one more character.
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -0,0 +1,328 @@
+use crate::database::{self, Pool};
+use crate::export::{ExportFormat, CSV_HEADER};
+use crate::fetch::Item;
+use crate::model::Record;
+use crate::{DriveExists, File, Folder, InvalidImport, ReadImport, Result};
+use futures::prelude::*;
+use snafu::{OptionExt, ResultExt};
+use std::convert::TryFrom;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+#[tracing::instrument(level = "debug", skip(reader, pool))]
+pub(crate) async fn import<R>(
+    drive_id: &str,
+    page_token: &str,
+    format: ExportFormat,
+    reader: R,
+    disambiguate: bool,
+    pool: &Pool,
+) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    if database::get_drive(drive_id, pool).await?.is_some() {
+        return Err(DriveExists { drive_id }.build().into());
+    }
+
+    // The root folder shares its id with the drive.
+    let state = (Records::new(format, reader), false);
+    let items = stream::try_unfold(state, |(mut records, root)| async move {
+        let record = match records.next().await? {
+            Some(record) => record,
+            None if root => return Ok(None),
+            None => {
+                return Err(InvalidImport {
+                    reason: "the root folder is missing",
+                }
+                .build()
+                .into())
+            }
+        };
+
+        if record.drive_id != drive_id {
+            return Err(InvalidImport {
+                reason: format!("{} belongs to Shared Drive {}", record.id, record.drive_id),
+            }
+            .build()
+            .into());
+        }
+
+        // Only the root folder has no parent.
+        let is_root = record.id == drive_id;
+        match (is_root, record.folder, &record.parent) {
+            (true, true, None) | (false, _, Some(_)) => (),
+            (true, _, _) => {
+                return Err(InvalidImport {
+                    reason: format!(
+                        "the root folder {} must be a folder without a parent",
+                        record.id
+                    ),
+                }
+                .build()
+                .into())
+            }
+            (false, _, None) => {
+                return Err(InvalidImport {
+                    reason: format!("{} has no parent", record.id),
+                }
+                .build()
+                .into())
+            }
+        }
+
+        Ok(Some((to_item(record)?, (records, root || is_root))))
+    });
+
+    database::import_drive(drive_id, page_token, items, disambiguate, pool).await
+}
+
+/// The records of an export, which are parsed one at a time.
+pub(crate) struct Records<R> {
+    reader: BufReader<R>,
+    format: ExportFormat,
+    started: bool,
+}
+
+impl<R> Records<R>
+where
+    R: AsyncRead + Unpin,
+{
+    pub(crate) fn new(format: ExportFormat, reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            format,
+            started: false,
+        }
+    }
+
+    pub(crate) async fn next(&mut self) -> Result<Option<Record>> {
+        let started = std::mem::replace(&mut self.started, true);
+
+        match self.format {
+            ExportFormat::Ndjson => self.next_line().await,
+            ExportFormat::Json => match self.next_object(started).await? {
+                Some(object) => from_json(&object).map(Some),
+                None => Ok(None),
+            },
+            ExportFormat::Csv => {
+                if !started {
+                    let header = self.next_row().await?.map(|row| row.join(",") + "\n");
+
+                    if header.as_deref() != Some(CSV_HEADER) {
+                        return Err(InvalidImport {
+                            reason: "unexpected CSV header",
+                        }
+                        .build()
+                        .into());
+                    }
+                }
+
+                self.next_row().await?.map(from_csv).transpose()
+            }
+        }
+    }
+
+    async fn next_line(&mut self) -> Result<Option<Record>> {
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            if self.reader.read_line(&mut line).await.context(ReadImport)? == 0 {
+                return Ok(None);
+            }
+
+            if !line.trim().is_empty() {
+                return from_json(line.as_bytes()).map(Some);
+            }
+        }
+    }
+
+    /// The next object of a JSON array, found by matching its braces outside of strings.
+    async fn next_object(&mut self, started: bool) -> Result<Option<Vec<u8>>> {
+        let mut object = Vec::new();
+        let mut chunk = Vec::new();
+        let (mut depth, mut string, mut escaped) = (0, false, false);
+        let mut opened = started;
+
+        loop {
+            // Every object ends with a closing brace.
+            chunk.clear();
+            if self
+                .reader
+                .read_until(b'}', &mut chunk)
+                .await
+                .context(ReadImport)?
+                == 0
+            {
+                return match (opened, depth) {
+                    (true, 0) => Ok(None),
+                    _ => Err(InvalidImport {
+                        reason: "the JSON array is incomplete",
+                    }
+                    .build()
+                    .into()),
+                };
+            }
+
+            for &byte in &chunk {
+                if depth == 0 {
+                    match byte {
+                        b'[' if !opened => opened = true,
+                        b',' if opened => (),
+                        b']' if opened => return Ok(None),
+                        b'{' if opened => {
+                            depth = 1;
+                            object.push(byte);
+                        }
+                        byte if byte.is_ascii_whitespace() => (),
+                        byte => {
+                            return Err(InvalidImport {
+                                reason: format!("unexpected {:?} in the JSON array", byte as char),
+                            }
+                            .build()
+                            .into())
+                        }
+                    }
+
+                    continue;
+                }
+
+                object.push(byte);
+
+                match (byte, string, escaped) {
+                    (_, true, true) => escaped = false,
+                    (b'\\', true, false) => escaped = true,
+                    (b'"', _, false) => string = !string,
+                    (b'{', false, _) => depth += 1,
+                    (b'}', false, _) => depth -= 1,
+                    _ => (),
+                }
+            }
+
+            if depth == 0 && !object.is_empty() {
+                return Ok(Some(object));
+            }
+        }
+    }
+
+    /// The fields of the next CSV row, where quoted fields may contain separators and newlines.
+    ///
+    /// Blank lines are skipped.
+    async fn next_row(&mut self) -> Result<Option<Vec<String>>> {
+        let mut row = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+
+            if self.reader.read_line(&mut line).await.context(ReadImport)? == 0 {
+                // The last row might not end with a newline.
+                if field.is_empty() && row.is_empty() {
+                    return Ok(None);
+                }
+
+                row.push(field);
+                return Ok(Some(row));
+            }
+
+            let mut chars = line.chars().peekable();
+
+            while let Some(c) = chars.next() {
+                match (c, quoted) {
+                    ('"', true) if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    ('"', _) => quoted = !quoted,
+                    (',', false) => row.push(std::mem::take(&mut field)),
+                    ('\r', false) => (),
+                    ('\n', false) if row.is_empty() && field.is_empty() => (),
+                    ('\n', false) => {
+                        row.push(field);
+                        return Ok(Some(row));
+                    }
+                    (c, _) => field.push(c),
+                }
+            }
+        }
+    }
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(json: &[u8]) -> Result<T> {
+    let value = serde_json::from_slice(json).map_err(|e| {
+        InvalidImport {
+            reason: e.to_string(),
+        }
+        .build()
+    })?;
+
+    Ok(value)
+}
+
+fn from_csv(row: Vec<String>) -> Result<Record> {
+    match <[String; 9]>::try_from(row) {
+        Ok([id, drive_id, parent, name, path, folder, trashed, md5, size]) => Ok(Record {
+            id,
+            drive_id,
+            parent: non_empty(parent),
+            name,
+            path: non_empty(path),
+            folder: parse_field(&folder)?,
+            trashed: parse_field(&trashed)?,
+            md5: non_empty(md5),
+            size: non_empty(size).map(|size| parse_field(&size)).transpose()?,
+        }),
+        Err(row) => Err(InvalidImport {
+            reason: format!("expected 9 fields, got {}", row.len()),
+        }
+        .build()
+        .into()),
+    }
+}
+
+fn non_empty(field: String) -> Option<String> {
+    match field.is_empty() {
+        true => None,
+        false => Some(field),
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(field: &str) -> Result<T> {
+    let value = field.parse().ok().context(InvalidImport {
+        reason: format!("invalid field {:?}", field),
+    })?;
+
+    Ok(value)
+}
+
+fn to_item(record: Record) -> Result<Item> {
+    let item = match record.folder {
+        true => Item::Folder(Folder {
+            id: record.id,
+            drive_id: record.drive_id,
+            name: record.name,
+            trashed: record.trashed,
+            parent: record.parent,
+        }),
+        false => {
+            let missing = |field| InvalidImport {
+                reason: format!("file {} has no {}", record.id, field),
+            };
+
+            Item::File(File {
+                parent: record.parent.clone().with_context(|| missing("parent"))?,
+                md5: record.md5.clone().with_context(|| missing("md5"))?,
+                size: record.size.with_context(|| missing("size"))?,
+                id: record.id,
+                drive_id: record.drive_id,
+                name: record.name,
+                trashed: record.trashed,
+            })
+        }
+    };
+
+    Ok(item)
+}
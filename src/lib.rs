@@ -1,30 +1,70 @@
-use database::Pool;
+use chrono::{Duration, Utc};
 use fetch::{FetchBuilder, Fetcher};
 use jsonwebtoken::EncodingKey;
+use lock::{Busy, DriveLocks, WriteGuard};
 use reqwest::IntoUrl;
 use serde::Deserialize;
-use snafu::{ResultExt, Snafu};
+use snafu::{OptionExt, ResultExt, Snafu};
 use std::convert::TryFrom;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::info;
+use tokio::time::Instant;
+use tracing::{info, warn};
 
 mod changes;
+mod check;
 mod database;
+mod diff;
+mod export;
 mod fetch;
+mod glob;
+mod import;
+mod lock;
+mod maintenance;
 mod model;
-
-pub use changes::Changes;
-pub use model::{ChangedFile, ChangedFolder, ChangedPath, File, Folder, InnerPath, Path};
+mod query;
+mod reader;
+mod safeguard;
+mod search;
+mod store;
+
+pub use changes::{Changes, Preview};
+pub use check::IntegrityReport;
+pub use database::{DatabaseChangelog, DatabaseOptions, DatabaseStore};
+#[cfg(not(feature = "postgres"))]
+pub use database::{JournalMode, Synchronous};
+pub use diff::{Difference, Snapshot};
+pub use export::ExportFormat;
+pub use fetch::{Change, Item, PartialDrive};
+pub use maintenance::Maintenance;
+pub use model::{
+    AsOf, ChangedFile, ChangedFolder, ChangedPath, ChangelogKey, Collision, Drive, Duplicate,
+    DuplicateSummary, File, Folder, FolderStats, InnerPath, Path, PendingChanges, Quarantined,
+    Record, SyncRun, Tombstone,
+};
+pub use query::{Duplicates, Query};
+pub use reader::BernardReader;
+pub use safeguard::{DeletionSummary, Safeguard};
+pub use store::{Changelog, Connect, MemoryStore, Merge, MergeOptions, Store};
 
 #[derive(Debug, Snafu)]
 pub struct Error(InnerError);
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ErrorKind {
+    ChangelogNotEmpty,
     Database,
+    DifferentDrives,
+    DriveExists,
+    DriveInUse,
+    Export,
+    Import,
+    MassDeletion,
     Network,
     PartialChangeList,
+    Store,
+    SyncInProgress,
+    UnknownDrive,
     WhereIsJWK,
     InvalidJWK,
 }
@@ -32,12 +72,41 @@ pub enum ErrorKind {
 #[derive(Debug, Snafu)]
 #[snafu(visibility(pub(crate)))]
 enum InnerError {
+    #[snafu(display("The changelog of Shared Drive {} would be discarded", drive_id))]
+    ChangelogNotEmpty { drive_id: String },
     #[snafu(display("Database"))]
     Database { source: sqlx::Error },
+    #[snafu(display("The Changes of Shared Drive {} are still in use", drive_id))]
+    DriveInUse { drive_id: String },
+    #[snafu(display("Shared Drive {} has already been synchronised", drive_id))]
+    DriveExists { drive_id: String },
+    #[snafu(display("Cannot compare Shared Drive {} with Shared Drive {}", old, new))]
+    DifferentDrives { old: String, new: String },
+    #[snafu(display("Cannot read the import"))]
+    ReadImport { source: std::io::Error },
+    #[snafu(display("Invalid import: {}", reason))]
+    InvalidImport { reason: String },
+    #[snafu(display("Cannot write the export"))]
+    WriteExport { source: std::io::Error },
+    #[snafu(display("Refusing to commit a mass deletion: {}", summary))]
+    MassDeletion { summary: DeletionSummary },
     #[snafu(display("Network"))]
     Network { source: fetch::Error },
     #[snafu(display("Received a partial change list from Google"))]
     PartialChangeList { source: sqlx::Error },
+    #[snafu(display(
+        "Received a partial change list from Google, the parent of {} is unknown",
+        id
+    ))]
+    MissingParent { id: String },
+    #[snafu(display("Store"))]
+    CustomStore {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[snafu(display("Shared Drive {} is being synchronised", drive_id))]
+    SyncInProgress { drive_id: String },
+    #[snafu(display("Shared Drive {} has not been synchronised yet", drive_id))]
+    UnknownDrive { drive_id: String },
     #[snafu(display("Cannot read the Service Account JWK file: {:?}", file_name))]
     WhereIsJWK {
         file_name: PathBuf,
@@ -55,16 +124,46 @@ impl Error {
         use InnerError::*;
 
         match self.0 {
+            ChangelogNotEmpty { .. } => ErrorKind::ChangelogNotEmpty,
             Database { .. } => ErrorKind::Database,
+            DriveInUse { .. } => ErrorKind::DriveInUse,
+            DriveExists { .. } => ErrorKind::DriveExists,
+            DifferentDrives { .. } => ErrorKind::DifferentDrives,
+            ReadImport { .. } => ErrorKind::Import,
+            InvalidImport { .. } => ErrorKind::Import,
+            WriteExport { .. } => ErrorKind::Export,
+            MassDeletion { .. } => ErrorKind::MassDeletion,
             Network { .. } => ErrorKind::Network,
             PartialChangeList { .. } => ErrorKind::PartialChangeList,
+            MissingParent { .. } => ErrorKind::PartialChangeList,
+            CustomStore { .. } => ErrorKind::Store,
+            SyncInProgress { .. } => ErrorKind::SyncInProgress,
+            UnknownDrive { .. } => ErrorKind::UnknownDrive,
             WhereIsJWK { .. } => ErrorKind::WhereIsJWK,
             InvalidJWK { .. } => ErrorKind::InvalidJWK,
         }
     }
 
+    /// An error of a custom [`Store`].
+    pub fn store<E>(source: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Self(InnerError::CustomStore {
+            source: source.into(),
+        })
+    }
+
     pub fn is_partial_change_list(&self) -> bool {
-        matches!(self.0, InnerError::PartialChangeList { .. })
+        self.kind() == ErrorKind::PartialChangeList
+    }
+
+    /// The deletions which caused the synchronisation to be rejected.
+    pub fn deletion_summary(&self) -> Option<&DeletionSummary> {
+        match &self.0 {
+            InnerError::MassDeletion { summary } => Some(summary),
+            _ => None,
+        }
     }
 }
 
@@ -73,7 +172,9 @@ impl From<sqlx::Error> for Error {
         match &source {
             sqlx::Error::Database(db_err) => match db_err.code() {
                 Some(code) => match code.as_ref() {
-                    "787" => Self(InnerError::PartialChangeList { source }),
+                    database::FOREIGN_KEY_VIOLATION => {
+                        Self(InnerError::PartialChangeList { source })
+                    }
                     _ => Self(InnerError::Database { source }),
                 },
                 _ => Self(InnerError::Database { source }),
@@ -91,32 +192,250 @@ impl From<fetch::Error> for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-pub struct Bernard {
+pub struct Bernard<S: Store = DatabaseStore> {
     fetch: Arc<Fetcher>,
-    pool: Pool,
+    store: S,
+    safeguard: Option<Safeguard>,
+    retention: Option<Duration>,
+    quarantine: bool,
+    locks: DriveLocks,
+    lease: Lease,
+}
+
+/// The lease on the synchronisation of a drive, which is shared with other processes.
+struct Lease {
+    holder: String,
+    duration: Duration,
+    wait: Option<Duration>,
 }
 
 // TODO: Better names
-pub enum SyncKind<'a> {
+pub enum SyncKind<'a, S: Store = DatabaseStore> {
     Full,
-    Partial(Changes<'a>),
+    Partial(Changes<'a, S>),
 }
 
 impl Bernard {
+    /// The database is the path to a SQLite file, or a Postgres connection URL
+    /// such as `postgres://localhost/bernard` with the `postgres` feature enabled.
+    /// The SQLite path `:memory:` keeps the database in memory until Bernard is closed,
+    /// on a single connection which `Changes` keep until they are dropped.
     pub fn builder<S: Into<String>>(database_path: S, account: Account) -> BernardBuilder {
         BernardBuilder::new(database_path, account)
     }
 
+    /// Query the files and folders stored in the database.
+    pub fn query(&self) -> Query<'_> {
+        Query::new(&self.store.pool)
+    }
+
+    /// Search the names and paths of files and folders, see [`Query::search`].
+    pub async fn search(&self, query: &str, drive_id: Option<&str>) -> Result<Vec<Path>> {
+        self.query().search(query, drive_id).await
+    }
+
+    /// Write every file and folder of a Shared Drive with its path, md5, size and trashed flag.
+    ///
+    /// The records are streamed from the database, so the export is never held in memory.
+    pub async fn export<W>(&self, drive_id: &str, format: ExportFormat, writer: W) -> Result<()>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        export::export(drive_id, format, writer, &self.store.pool).await
+    }
+
+    /// Add a Shared Drive from an export taken at `page_token`, instead of a full synchronisation.
+    ///
+    /// The import does not leave a changelog behind,
+    /// so the next synchronisation continues with the changes since the export.
+    /// The export is parsed and inserted in batches, so it is never held in memory.
+    pub async fn import<R>(
+        &self,
+        drive_id: &str,
+        page_token: &str,
+        format: ExportFormat,
+        reader: R,
+    ) -> Result<()>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+    {
+        let deadline = self.lease_deadline();
+        let _guard = self.lock_drive(drive_id, deadline).await?;
+        self.acquire_lease(drive_id, deadline).await?;
+
+        let disambiguate = self.store.disambiguate_paths;
+        let result = import::import(
+            drive_id,
+            page_token,
+            format,
+            reader,
+            disambiguate,
+            &self.store.pool,
+        )
+        .await;
+
+        self.release_lease(drive_id).await;
+        result
+    }
+
+    /// Write a consistent copy of the SQLite database to `path`, which must not exist yet.
+    ///
+    /// Unlike copying the database file and its `.wal` file,
+    /// the backup can be taken while Shared Drives are being synchronised.
+    /// The copy is written by `VACUUM INTO` within a single read transaction,
+    /// rather than by the online backup API, so it is compacted as well.
+    #[cfg(not(feature = "postgres"))]
+    pub async fn backup_to<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref().to_string_lossy();
+        maintenance::backup(&path, &self.store.pool).await
+    }
+
+    /// Look for anomalies which the paths of a Shared Drive cannot be derived from,
+    /// such as cycles and folders which are missing.
+    pub async fn check(&self, drive_id: &str) -> Result<IntegrityReport> {
+        check::check(drive_id, &self.store.pool).await
+    }
+
+    /// Fetch the items which cause the anomalies of the report again and merge them,
+    /// returning the changes this made like a synchronisation.
+    ///
+    /// The changelog is replaced, which also removes any stale changes.
+    /// Unless `discard_changelog` is set, the repair fails with [`ErrorKind::ChangelogNotEmpty`]
+    /// instead of discarding the changes of the last synchronisation, so they can be read first.
+    /// Fails as a partial change list if a refetched item still has a missing parent,
+    /// in which case the drive has to be removed and synchronised again,
+    /// unless such items are quarantined.
+    #[tracing::instrument(level = "info", skip(self, report), fields(drive_id = %report.drive_id))]
+    pub async fn repair<'a>(
+        &'a self,
+        report: &'a IntegrityReport,
+        discard_changelog: bool,
+    ) -> Result<Changes<'a>> {
+        let drive_id = report.drive_id.as_str();
+
+        let deadline = self.lease_deadline();
+        let guard = self.lock_drive(drive_id, deadline).await?;
+        self.acquire_lease(drive_id, deadline).await?;
+
+        let result = self.repair_leased(report, discard_changelog).await;
+
+        self.release_lease(drive_id).await;
+        result?;
+
+        self.changes(drive_id, guard).await
+    }
+
+    async fn repair_leased(&self, report: &IntegrityReport, discard_changelog: bool) -> Result<()> {
+        let drive_id = report.drive_id.as_str();
+
+        let drive = self
+            .store
+            .get_drive(drive_id)
+            .await?
+            .context(UnknownDrive { drive_id })?;
+
+        if !discard_changelog {
+            let mut changelog = self.store.changelog(drive_id).await?;
+
+            if !changelog.get_changed_folders(None, 1).await?.is_empty()
+                || !changelog.get_changed_files(None, 1).await?.is_empty()
+            {
+                return Err(ChangelogNotEmpty { drive_id }.build().into());
+            }
+        }
+
+        let mut changes = Vec::new();
+
+        for id in report.causes() {
+            let change = match self.fetch.clone().item(id).await? {
+                Some(item) if item.drive_id() == drive_id => Change::ItemChanged(item),
+                _ => Change::ItemRemoved(id.to_owned()),
+            };
+
+            changes.push(change);
+        }
+
+        info!(count = changes.len(), "refetched items");
+
+        self.store
+            .merge_changes(
+                drive_id,
+                &changes,
+                &drive.page_token,
+                &self.merge_options(None),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Compact the database and refresh the statistics of the query planner,
+    /// returning how much space was reclaimed.
+    ///
+    /// The changelog of removed Shared Drives is cleared beforehand.
+    ///
+    /// In SQLite this runs `ANALYZE`, `VACUUM` and `wal_checkpoint(TRUNCATE)`,
+    /// and the sizes include the WAL file.
+    /// In Postgres this runs `VACUUM (ANALYZE)`.
+    ///
+    /// The maintenance takes the lock and the lease of every Shared Drive like a synchronisation,
+    /// so synchronisations wait for it to finish rather than failing with a busy database.
+    /// Fails with `DriveInUse` while the `Changes` of a Shared Drive are still alive.
+    pub async fn maintain(&self) -> Result<Maintenance> {
+        let drives = database::get_drives(&self.store.pool).await?;
+        let deadline = self.lease_deadline();
+
+        let mut guards = Vec::with_capacity(drives.len());
+        let mut leased = Vec::with_capacity(drives.len());
+
+        let result = async {
+            for drive in &drives {
+                guards.push(self.lock_drive(&drive.id, deadline).await?);
+                self.acquire_lease(&drive.id, deadline).await?;
+                leased.push(drive.id.as_str());
+            }
+
+            maintenance::maintain(&self.store.pool).await
+        }
+        .await;
+
+        for drive_id in leased {
+            self.release_lease(drive_id).await;
+        }
+
+        result
+    }
+}
+
+impl<S: Store> Bernard<S> {
+    /// Keep the metadata in another store than the database, such as a [`MemoryStore`].
+    pub fn with_store(store: S, account: Account) -> BernardBuilder<S> {
+        BernardBuilder::with_connect(store, account)
+    }
+
     pub async fn close(self) {
-        self.pool.close().await
+        self.store.close().await
     }
 
     #[tracing::instrument(level = "info", skip(self))]
-    pub async fn sync_drive<'a>(&'a self, drive_id: &'a str) -> Result<SyncKind<'a>> {
-        // Always clear changelog for consistent database state when sync_drive is called.
-        database::clear_changelog(drive_id, &self.pool).await?;
+    pub async fn sync_drive<'a>(&'a self, drive_id: &'a str) -> Result<SyncKind<'a, S>> {
+        let deadline = self.lease_deadline();
+        let guard = self.lock_drive(drive_id, deadline).await?;
+        self.acquire_lease(drive_id, deadline).await?;
 
-        let drive = database::get_drive(drive_id, &self.pool).await?;
+        let result = self.sync_drive_leased(drive_id, guard).await;
+
+        self.release_lease(drive_id).await;
+        result
+    }
+
+    async fn sync_drive_leased<'a>(
+        &'a self,
+        drive_id: &'a str,
+        guard: WriteGuard,
+    ) -> Result<SyncKind<'a, S>> {
+        // The changelog is replaced by the merge, so readers see the previous one in the meantime.
+        let drive = self.store.get_drive(drive_id).await?;
 
         match drive {
             None => {
@@ -127,7 +446,10 @@ impl Bernard {
                 let name = self.fetch.clone().drive_name(drive_id).await?;
                 let items = self.fetch.clone().all_files(drive_id).await?;
 
-                database::add_drive(drive_id, &name, &page_token, items, &self.pool).await?;
+                self.renew_lease(drive_id).await?;
+                self.store
+                    .add_drive(drive_id, &name, &page_token, items)
+                    .await?;
 
                 Ok(SyncKind::Full)
             }
@@ -137,47 +459,343 @@ impl Bernard {
                 let (changes, new_page_token) = self
                     .fetch
                     .clone()
-                    .changes(drive_id, &drive.page_token)
+                    .changes(&drive.id, &drive.page_token)
                     .await?;
 
                 match new_page_token == drive.page_token {
                     // Do not perform database operation if no changes are available.
                     true => {
                         info!(page_token = %new_page_token, "page token has not changed");
+                        self.store.clear_changelog(drive_id).await?;
                     }
                     false => {
                         info!(page_token = %new_page_token, "page token has changed");
-                        database::merge_changes(drive_id, changes, &new_page_token, &self.pool)
+                        self.renew_lease(drive_id).await?;
+
+                        let merge = self
+                            .store
+                            .merge_changes(
+                                drive_id,
+                                &changes,
+                                &new_page_token,
+                                &self.merge_options(self.safeguard.as_ref()),
+                            )
                             .await?;
+
+                        match merge {
+                            Merge::Committed => {
+                                self.store.clear_pending_changes(drive_id).await?;
+                            }
+                            Merge::Rejected(summary) => {
+                                warn!(%summary, "refusing to commit a mass deletion");
+
+                                self.store
+                                    .save_pending_changes(drive_id, &changes, &new_page_token)
+                                    .await?;
+
+                                return Err(MassDeletion { summary }.build().into());
+                            }
+                        }
                     }
                 };
 
-                Ok(SyncKind::Partial(Changes::new(self, drive_id)))
+                let changes = self.changes(drive_id, guard).await?;
+                Ok(SyncKind::Partial(changes))
+            }
+        }
+    }
+
+    /// When to stop waiting for other synchronisations of a drive, see [`BernardBuilder::wait_for_sync`].
+    fn lease_deadline(&self) -> Instant {
+        let wait = self.lease.wait.and_then(|wait| wait.to_std().ok());
+        Instant::now() + wait.unwrap_or_default()
+    }
+
+    /// Take the in-process lock of the drive, waiting for a synchronisation in progress until the deadline.
+    ///
+    /// Fails with `DriveInUse` instead while the `Changes` of the drive are still alive.
+    async fn lock_drive(&self, drive_id: &str, deadline: Instant) -> Result<WriteGuard> {
+        match self.locks.write(drive_id, deadline).await {
+            Ok(guard) => Ok(guard),
+            Err(Busy::Synchronising) => Err(SyncInProgress { drive_id }.build().into()),
+            Err(Busy::InUse) => Err(DriveInUse { drive_id }.build().into()),
+        }
+    }
+
+    /// Take the lease on the synchronisation of the drive, so other processes cannot synchronise it.
+    ///
+    /// Waits for the lease of another process to be released or to expire until the deadline,
+    /// then fails with `SyncInProgress`.
+    async fn acquire_lease(&self, drive_id: &str, deadline: Instant) -> Result<()> {
+        loop {
+            if self.try_acquire_lease(drive_id).await? {
+                return Ok(());
+            }
+
+            match Instant::now() < deadline {
+                true => tokio::time::sleep(LEASE_POLL_INTERVAL).await,
+                false => return Err(SyncInProgress { drive_id }.build().into()),
             }
         }
     }
+
+    /// Extend the lease right before writing,
+    /// in case another process took over the lease once it expired.
+    async fn renew_lease(&self, drive_id: &str) -> Result<()> {
+        match self.try_acquire_lease(drive_id).await? {
+            true => Ok(()),
+            false => Err(SyncInProgress { drive_id }.build().into()),
+        }
+    }
+
+    async fn try_acquire_lease(&self, drive_id: &str) -> Result<bool> {
+        self.store
+            .acquire_lease(drive_id, &self.lease.holder, self.lease.duration)
+            .await
+    }
+
+    /// Merge with the lease, so the merge fails if another process took it over in the meantime.
+    fn merge_options<'a>(&'a self, safeguard: Option<&'a Safeguard>) -> MergeOptions<'a> {
+        MergeOptions {
+            safeguard,
+            retention: self.retention,
+            quarantine: self.quarantine,
+            holder: Some(&self.lease.holder),
+        }
+    }
+
+    /// Give up the lease, logging rather than returning a failure,
+    /// which would mask the result of the operation which held the lease.
+    /// A lease which could not be released expires on its own.
+    async fn release_lease(&self, drive_id: &str) {
+        let released = self.store.release_lease(drive_id, &self.lease.holder).await;
+
+        if let Err(error) = released {
+            warn!(%error, "cannot release the lease");
+        }
+    }
+
+    /// Take a snapshot of the changelog while still holding the lock of the synchronisation.
+    async fn changes<'a>(&self, drive_id: &'a str, guard: WriteGuard) -> Result<Changes<'a, S>> {
+        let guard = guard.downgrade();
+        let changelog = self.store.changelog(drive_id).await?;
+
+        Ok(Changes::new(drive_id, changelog, Some(guard)))
+    }
+
+    /// Fetch the changes since the last synchronisation without committing them.
+    ///
+    /// The page token and the changelog of the previous synchronisation remain untouched.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn preview_sync(&self, drive_id: &str) -> Result<Preview> {
+        let drive = self
+            .store
+            .get_drive(drive_id)
+            .await?
+            .context(UnknownDrive { drive_id })?;
+
+        let (changes, page_token) = self
+            .fetch
+            .clone()
+            .changes(&drive.id, &drive.page_token)
+            .await?;
+
+        info!(%page_token, "previewing changes");
+        let preview = self
+            .store
+            .preview_changes(drive_id, &changes, self.quarantine)
+            .await?;
+
+        Ok(preview)
+    }
+
+    /// Commit the change list which was rejected by the mass-deletion safeguard.
+    ///
+    /// The rejected change list is kept in the store, so it can be accepted after a restart,
+    /// and is replaced by the next synchronisation of the Shared Drive.
+    /// Returns `None` if no change list of this Shared Drive is pending.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn accept_pending_changes<'a>(
+        &'a self,
+        drive_id: &'a str,
+    ) -> Result<Option<Changes<'a, S>>> {
+        let deadline = self.lease_deadline();
+        let guard = self.lock_drive(drive_id, deadline).await?;
+        self.acquire_lease(drive_id, deadline).await?;
+
+        let result = self.merge_pending_changes(drive_id).await;
+
+        self.release_lease(drive_id).await;
+
+        match result? {
+            true => Ok(Some(self.changes(drive_id, guard).await?)),
+            false => Ok(None),
+        }
+    }
+
+    /// Returns `false` if no change list of this Shared Drive is pending.
+    async fn merge_pending_changes(&self, drive_id: &str) -> Result<bool> {
+        let pending = self.store.pending_changes(drive_id).await?;
+
+        let PendingChanges {
+            changes,
+            page_token,
+        } = match pending {
+            Some(pending) => pending,
+            None => return Ok(false),
+        };
+
+        info!(%page_token, "accepting pending changes");
+
+        self.store
+            .merge_changes(drive_id, &changes, &page_token, &self.merge_options(None))
+            .await?;
+
+        self.store.clear_pending_changes(drive_id).await?;
+
+        Ok(true)
+    }
+
+    /// Forget a Shared Drive and all of its files and folders.
+    ///
+    /// With `emit_changes`, the removal is returned as `Changes` in which every item is deleted,
+    /// otherwise the Shared Drive is removed silently and `None` is returned.
+    /// The deletions stay in the database until the next removal of a Shared Drive
+    /// or until [`Bernard::maintain`], unless the Shared Drive is synchronised again.
+    #[tracing::instrument(level = "info", skip(self))]
+    pub async fn remove_drive<'a>(
+        &'a self,
+        drive_id: &'a str,
+        emit_changes: bool,
+    ) -> Result<Option<Changes<'a, S>>> {
+        let deadline = self.lease_deadline();
+        let guard = self.lock_drive(drive_id, deadline).await?;
+        self.acquire_lease(drive_id, deadline).await?;
+
+        let result = self.remove_drive_leased(drive_id, emit_changes).await;
+
+        self.release_lease(drive_id).await;
+        result?;
+
+        info!("removed drive");
+
+        match emit_changes {
+            true => Ok(Some(self.changes(drive_id, guard).await?)),
+            false => Ok(None),
+        }
+    }
+
+    async fn remove_drive_leased(&self, drive_id: &str, emit_changes: bool) -> Result<()> {
+        self.store
+            .get_drive(drive_id)
+            .await?
+            .context(UnknownDrive { drive_id })?;
+
+        self.store.remove_drive(drive_id, emit_changes).await
+    }
 }
 
-pub struct BernardBuilder {
-    database_path: String,
+pub struct BernardBuilder<C: Connect = DatabaseOptions> {
+    connect: C,
     fetch: FetchBuilder,
+    safeguard: Option<Safeguard>,
+    retention: Option<Duration>,
+    quarantine: bool,
+    lease: Duration,
+    lease_wait: Option<Duration>,
 }
 
 impl BernardBuilder {
     pub fn new<S: Into<String>>(database_path: S, account: Account) -> Self {
+        Self::with_connect(DatabaseOptions::new(database_path), account)
+    }
+
+    /// The maximum number of connections to the database, 10 by default.
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.connect.max_connections = Some(max);
+        self
+    }
+
+    /// Suffix the names which siblings share with the id of each item in their paths,
+    /// such as `Season 1 (id)` or `movie (id).mkv`, so every path is unique.
+    ///
+    /// The disambiguated names apply to the paths of queries, of the history and of the changelog alike,
+    /// whereas [`Query::name_collisions`] reports the colliding names themselves.
+    /// Switching this on or off rebuilds the paths of a drive on its next synchronisation.
+    /// An item of which the path gains or loses its id is listed in the changelog as deleted and created,
+    /// even if the item itself did not change.
+    pub fn disambiguate_paths(mut self) -> Self {
+        self.connect.disambiguate_paths = true;
+        self
+    }
+
+    /// Defaults to `JournalMode::Wal`.
+    #[cfg(not(feature = "postgres"))]
+    pub fn journal_mode(mut self, mode: JournalMode) -> Self {
+        self.connect.journal_mode = Some(mode);
+        self
+    }
+
+    /// Defaults to `Synchronous::Full`, whereas `Synchronous::Normal` is safe in WAL mode
+    /// and considerably faster.
+    #[cfg(not(feature = "postgres"))]
+    pub fn synchronous(mut self, synchronous: Synchronous) -> Self {
+        self.connect.synchronous = Some(synchronous);
+        self
+    }
+
+    /// How long to wait for another connection to release its lock on the database,
+    /// 5 seconds by default.
+    #[cfg(not(feature = "postgres"))]
+    pub fn busy_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.connect.busy_timeout = Some(timeout);
+        self
+    }
+
+    /// The size of the page cache of each connection in kibibytes.
+    #[cfg(not(feature = "postgres"))]
+    pub fn cache_size(mut self, kibibytes: u64) -> Self {
+        self.connect.cache_size = Some(kibibytes);
+        self
+    }
+
+    /// The amount of the database file in bytes which is memory-mapped, 0 disables it.
+    #[cfg(not(feature = "postgres"))]
+    pub fn mmap_size(mut self, bytes: u64) -> Self {
+        self.connect.mmap_size = Some(bytes);
+        self
+    }
+}
+
+impl<C: Connect> BernardBuilder<C> {
+    fn with_connect(connect: C, account: Account) -> Self {
         Self {
-            database_path: database_path.into(),
+            connect,
             fetch: Fetcher::builder(account),
+            safeguard: None,
+            retention: None,
+            quarantine: false,
+            lease: Duration::minutes(DEFAULT_LEASE_MINUTES),
+            lease_wait: None,
         }
     }
 
     // Instead of build, simply call .await?
-    pub async fn build(self) -> Result<Bernard> {
-        let pool = database::establish_connection(&self.database_path).await?;
+    pub async fn build(self) -> Result<Bernard<C::Store>> {
+        let store = self.connect.connect().await?;
 
         Ok(Bernard {
             fetch: Arc::new(self.fetch.build()),
-            pool,
+            store,
+            safeguard: self.safeguard,
+            retention: self.retention,
+            quarantine: self.quarantine,
+            locks: DriveLocks::default(),
+            lease: Lease {
+                holder: lease_holder(),
+                duration: self.lease,
+                wait: self.lease_wait,
+            },
         })
     }
 
@@ -185,6 +803,67 @@ impl BernardBuilder {
         self.fetch = self.fetch.proxy(url);
         self
     }
+
+    /// Reject partial synchronisations which delete or trash more than `count` files.
+    pub fn max_deletions(mut self, count: u64) -> Self {
+        self.safeguard.get_or_insert_with(Default::default).count = Some(count);
+        self
+    }
+
+    /// Reject partial synchronisations which delete or trash more than `percentage`
+    /// percent of the files in the Shared Drive.
+    pub fn max_deletion_percentage(mut self, percentage: f64) -> Self {
+        self.safeguard
+            .get_or_insert_with(Default::default)
+            .percentage = Some(percentage);
+        self
+    }
+
+    /// Prune the tombstones of deleted items after the given duration,
+    /// otherwise they are kept indefinitely.
+    pub fn tombstone_retention(mut self, retention: Duration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Quarantine the files and folders of which the parent is unknown,
+    /// instead of failing the synchronisation as a partial change list.
+    ///
+    /// The rest of the changes are committed, whereas the quarantined items
+    /// are retried on every later synchronisation until their parent is known.
+    /// See [`Query::quarantined`].
+    pub fn quarantine_unknown_parents(mut self) -> Self {
+        self.quarantine = true;
+        self
+    }
+
+    /// Other processes may take over the synchronisation of a drive once its lease has expired,
+    /// such as when the process holding it crashed. Defaults to 10 minutes.
+    ///
+    /// The lease is renewed before writing the fetched changes,
+    /// so it should outlast fetching the changes of a single synchronisation.
+    pub fn sync_lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// Wait up to `timeout` for another synchronisation of the same drive to finish,
+    /// in this process or another, instead of failing with `SyncInProgress` right away.
+    pub fn wait_for_sync(mut self, timeout: Duration) -> Self {
+        self.lease_wait = Some(timeout);
+        self
+    }
+}
+
+const DEFAULT_LEASE_MINUTES: i64 = 10;
+
+/// How often a waiting synchronisation checks whether the lease has been released.
+const LEASE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Identifies this instance of Bernard as the holder of its leases.
+fn lease_holder() -> String {
+    let started = Utc::now().timestamp_nanos();
+    format!("{}-{}", std::process::id(), started)
 }
 
 #[derive(Debug, Deserialize)]
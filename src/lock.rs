@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+use tokio::time::Instant;
+
+pub(crate) type ReadGuard = OwnedRwLockReadGuard<()>;
+pub(crate) type WriteGuard = OwnedRwLockWriteGuard<()>;
+
+/// In-process locks per Shared Drive.
+///
+/// A synchronisation holds the write lock, whereas `Changes` hold a read lock
+/// for as long as their snapshot is alive.
+#[derive(Default)]
+pub(crate) struct DriveLocks(Mutex<HashMap<String, Arc<RwLock<()>>>>);
+
+impl DriveLocks {
+    fn get(&self, drive_id: &str) -> Arc<RwLock<()>> {
+        let mut locks = self.0.lock().unwrap();
+
+        locks.entry(drive_id.to_owned()).or_default().clone()
+    }
+
+    /// Waits until the deadline for the synchronisations and snapshots of the drive to finish.
+    pub(crate) async fn write(
+        &self,
+        drive_id: &str,
+        deadline: Instant,
+    ) -> Result<WriteGuard, Busy> {
+        let lock = self.get(drive_id);
+
+        match tokio::time::timeout_at(deadline, lock.clone().write_owned()).await {
+            Ok(guard) => Ok(guard),
+            // Snapshots only take read locks, so a drive which can be read is not being synchronised.
+            Err(_) => match lock.try_read() {
+                Ok(_) => Err(Busy::InUse),
+                Err(_) => Err(Busy::Synchronising),
+            },
+        }
+    }
+}
+
+/// Why the write lock of a drive could not be taken.
+#[derive(Debug)]
+pub(crate) enum Busy {
+    Synchronising,
+    InUse,
+}
@@ -0,0 +1,123 @@
+use crate::database::{self, Pool};
+use crate::Result;
+#[cfg(not(feature = "postgres"))]
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode};
+#[cfg(not(feature = "postgres"))]
+use sqlx::{ConnectOptions, Connection};
+use tracing::info;
+
+/// The size of the database before and after maintenance, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Maintenance {
+    pub size_before: i64,
+    pub size_after: i64,
+}
+
+impl Maintenance {
+    /// The space maintenance freed up, which is 0 if the database grew in the meantime.
+    pub fn reclaimed(&self) -> i64 {
+        (self.size_before - self.size_after).max(0)
+    }
+}
+
+/// Copy the database within a single read transaction,
+/// hence the backup is consistent and synchronisations can continue in WAL mode.
+#[cfg(not(feature = "postgres"))]
+#[tracing::instrument(level = "debug", skip(pool))]
+pub(crate) async fn backup(path: &str, pool: &Pool) -> Result<()> {
+    // Not checked, VACUUM cannot be described.
+    sqlx::query("VACUUM INTO $1")
+        .bind(path)
+        .execute(pool)
+        .await?;
+
+    // The copy has a rollback journal, whereas readers expect a database in WAL mode.
+    let options = SqliteConnectOptions::default()
+        .journal_mode(SqliteJournalMode::Wal)
+        .filename(path);
+    options.connect().await?.close().await?;
+
+    info!("backed up database");
+    Ok(())
+}
+
+#[cfg(not(feature = "postgres"))]
+#[tracing::instrument(level = "debug", skip(pool))]
+pub(crate) async fn maintain(pool: &Pool) -> Result<Maintenance> {
+    let size_before = size(pool).await?;
+    database::clear_removed_changelogs(&mut *pool.acquire().await?).await?;
+
+    // VACUUM rewrites the database through the WAL, which is truncated afterwards.
+    // Not checked, see backup.
+    sqlx::query("ANALYZE").execute(pool).await?;
+    sqlx::query("VACUUM").execute(pool).await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(pool)
+        .await?;
+
+    let size_after = size(pool).await?;
+    let maintenance = Maintenance {
+        size_before,
+        size_after,
+    };
+
+    info!(reclaimed = maintenance.reclaimed(), "maintained database");
+    Ok(maintenance)
+}
+
+/// The size of the database file and of its WAL file, if any.
+#[cfg(not(feature = "postgres"))]
+async fn size(pool: &Pool) -> Result<i64> {
+    // Not checked, see backup.
+    let (size, file): (i64, String) = sqlx::query_as(
+        "
+        SELECT c.page_count * s.page_size, d.file
+        FROM pragma_page_count() c, pragma_page_size() s, pragma_database_list() d
+        WHERE d.name = 'main'
+        ",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    // The file name is empty for an in-memory database,
+    // and the WAL file is missing in the other journal modes.
+    let wal = match file.is_empty() {
+        true => 0,
+        false => tokio::fs::metadata(format!("{}-wal", file))
+            .await
+            .map_or(0, |metadata| metadata.len() as i64),
+    };
+
+    Ok(size + wal)
+}
+
+/// Plain VACUUM marks the space of deleted rows for reuse without locking the tables,
+/// so the size only shrinks if the tail of a table is empty.
+#[cfg(feature = "postgres")]
+#[tracing::instrument(level = "debug", skip(pool))]
+pub(crate) async fn maintain(pool: &Pool) -> Result<Maintenance> {
+    let size_before = size(pool).await?;
+    database::clear_removed_changelogs(&mut *pool.acquire().await?).await?;
+
+    // Not checked, VACUUM cannot be described.
+    sqlx::query("VACUUM (ANALYZE)").execute(pool).await?;
+
+    let size_after = size(pool).await?;
+    let maintenance = Maintenance {
+        size_before,
+        size_after,
+    };
+
+    info!(reclaimed = maintenance.reclaimed(), "maintained database");
+    Ok(maintenance)
+}
+
+#[cfg(feature = "postgres")]
+async fn size(pool: &Pool) -> Result<i64> {
+    // Not checked, see maintain.
+    let (size,): (i64,) = sqlx::query_as("SELECT pg_database_size(current_database())")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(size)
+}
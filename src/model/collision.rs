@@ -0,0 +1,89 @@
+use super::{InnerPath, Path};
+use crate::database::Connection;
+use futures::prelude::*;
+use sqlx::Result;
+
+/// Files and folders with the same name within the same folder.
+#[derive(Debug)]
+pub struct Collision {
+    pub parent: String,
+    pub name: String,
+    /// The amount of items, which might exceed the amount of paths if some items are unreachable.
+    pub items: i64,
+    pub paths: Vec<Path>,
+}
+
+#[derive(sqlx::FromRow)]
+struct CollisionRow {
+    parent: String,
+    name: String,
+    id: String,
+    drive_id: String,
+    folder: bool,
+    trashed: bool,
+    path: Option<String>,
+}
+
+impl Collision {
+    /// The names shared by siblings, ordered by parent and name.
+    pub(crate) async fn get_all(drive_id: &str, conn: &mut Connection) -> Result<Vec<Self>> {
+        // Not checked, the macros cannot describe this compound SELECT.
+        let mut rows = sqlx::query_as::<_, CollisionRow>(
+            "
+            WITH items AS (
+                SELECT f.id, TRUE as folder, f.trashed, f.parent, f.name FROM folders f
+                WHERE f.drive_id = $1 AND f.parent IS NOT NULL
+                UNION ALL
+                SELECT f.id, FALSE, f.trashed, f.parent, f.name FROM files f
+                WHERE f.drive_id = $1
+            ),
+            collisions AS (
+                SELECT parent, name FROM items
+                GROUP BY parent, name
+                HAVING COUNT(*) > 1
+            )
+            SELECT i.parent, i.name, i.id, $1 as drive_id, i.folder, i.trashed, p.path FROM items i
+            INNER JOIN collisions c ON c.parent = i.parent AND c.name = i.name
+            LEFT JOIN item_paths p ON p.id = i.id AND p.drive_id = $1
+            ORDER BY i.parent, i.name, i.id
+            ",
+        )
+        .bind(drive_id)
+        .fetch(conn);
+
+        let mut collisions: Vec<Self> = Vec::new();
+
+        while let Some(row) = rows.try_next().await? {
+            let collision = match collisions.last_mut() {
+                Some(last) if last.parent == row.parent && last.name == row.name => last,
+                _ => {
+                    collisions.push(Self {
+                        parent: row.parent,
+                        name: row.name,
+                        items: 0,
+                        paths: Vec::new(),
+                    });
+                    collisions.last_mut().unwrap()
+                }
+            };
+
+            collision.items += 1;
+
+            if let Some(path) = row.path {
+                let inner_path = InnerPath {
+                    id: row.id,
+                    drive_id: row.drive_id,
+                    path: path.into(),
+                    trashed: row.trashed,
+                };
+
+                collision.paths.push(match row.folder {
+                    true => Path::Folder(inner_path),
+                    false => Path::File(inner_path),
+                });
+            }
+        }
+
+        Ok(collisions)
+    }
+}
@@ -1,6 +1,6 @@
-use crate::database::{Connection, Pool};
+use crate::database::Connection;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Drive {
     pub id: String,
     pub page_token: String,
@@ -23,12 +23,27 @@ impl Drive {
         Ok(())
     }
 
-    pub(crate) async fn get_by_id(id: &str, pool: &Pool) -> sqlx::Result<Option<Self>> {
-        sqlx::query_as!(Self, "SELECT * FROM drives WHERE id = $1", id)
-            .fetch_optional(pool)
+    pub(crate) async fn get_by_id(id: &str, conn: &mut Connection) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(Self, "SELECT id, page_token FROM drives WHERE id = $1", id)
+            .fetch_optional(conn)
             .await
     }
 
+    pub(crate) async fn get_all(conn: &mut Connection) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(Self, "SELECT id, page_token FROM drives ORDER BY id")
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Deleting a drive cascades to all of its items, which end up in the changelog.
+    pub(crate) async fn delete(id: &str, conn: &mut Connection) -> sqlx::Result<()> {
+        sqlx::query!("DELETE FROM drives WHERE id = $1", id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
     pub(crate) async fn update_page_token(
         id: &str,
         page_token: &str,
@@ -44,4 +59,22 @@ impl Drive {
 
         Ok(())
     }
+
+    /// Switch the disambiguation of the paths, returning whether it differs from before.
+    pub(crate) async fn set_disambiguate_paths(
+        id: &str,
+        disambiguate: bool,
+        conn: &mut Connection,
+    ) -> sqlx::Result<bool> {
+        let updated = sqlx::query!(
+            "UPDATE drives SET disambiguate_paths = $2 WHERE id = $1 AND disambiguate_paths <> $2",
+            id,
+            disambiguate
+        )
+        .execute(conn)
+        .await?
+        .rows_affected();
+
+        Ok(updated > 0)
+    }
 }
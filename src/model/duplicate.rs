@@ -0,0 +1,111 @@
+use super::Path;
+use crate::database::Connection;
+use sqlx::Result;
+
+/// Files with the same md5 checksum and size, which are not trashed.
+#[derive(Debug)]
+pub struct Duplicate {
+    pub md5: String,
+    pub size: i64,
+    /// The amount of files, which might exceed the amount of paths if some files are unreachable.
+    pub files: i64,
+    pub paths: Vec<Path>,
+}
+
+impl Duplicate {
+    /// The bytes which would be freed by keeping a single copy.
+    pub fn reclaimable(&self) -> i64 {
+        self.size * (self.files - 1)
+    }
+}
+
+#[derive(Debug)]
+pub struct DuplicateSummary {
+    pub groups: i64,
+    pub files: i64,
+    pub reclaimable: i64,
+}
+
+pub(crate) struct DuplicateFilter<'a> {
+    pub drive_id: Option<&'a str>,
+    pub min_size: i64,
+    /// The size and md5 of the last group of the previous page.
+    pub after: Option<(i64, &'a str)>,
+    pub limit: i64,
+}
+
+struct DuplicateRow {
+    md5: String,
+    size: i64,
+    files: i64,
+}
+
+impl Duplicate {
+    /// Groups of duplicate files, ordered by descending size.
+    pub(crate) async fn get_page(
+        filter: &DuplicateFilter<'_>,
+        conn: &mut Connection,
+    ) -> Result<Vec<Self>> {
+        let after_size = filter.after.map(|(size, _)| size);
+        let after_md5 = filter.after.map(|(_, md5)| md5);
+
+        let rows = sqlx::query_as!(
+            DuplicateRow,
+            r#"
+            SELECT md5, size, COUNT(*) as "files!: i64" FROM files
+            WHERE (CAST($1 AS TEXT) IS NULL OR drive_id = $1) AND size >= $2 AND NOT trashed
+                AND (CAST($3 AS BIGINT) IS NULL OR size < $3 OR (size = $3 AND md5 > $4))
+            GROUP BY size, md5
+            HAVING COUNT(*) > 1
+            ORDER BY size DESC, md5
+            LIMIT $5
+            "#,
+            filter.drive_id,
+            filter.min_size,
+            after_size,
+            after_md5,
+            filter.limit
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut duplicates = Vec::with_capacity(rows.len());
+
+        for row in rows {
+            let paths = Path::get_by_content(&row.md5, row.size, filter.drive_id, conn).await?;
+
+            duplicates.push(Self {
+                md5: row.md5,
+                size: row.size,
+                files: row.files,
+                paths,
+            });
+        }
+
+        Ok(duplicates)
+    }
+}
+
+impl DuplicateSummary {
+    pub(crate) async fn get(filter: &DuplicateFilter<'_>, conn: &mut Connection) -> Result<Self> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT
+                COUNT(*) as "groups!: i64",
+                CAST(COALESCE(SUM(d.files), 0) AS BIGINT) as "files!: i64",
+                CAST(COALESCE(SUM(d.size * (d.files - 1)), 0) AS BIGINT) as "reclaimable!: i64"
+            FROM (
+                SELECT size, COUNT(*) as files FROM files
+                WHERE (CAST($1 AS TEXT) IS NULL OR drive_id = $1) AND size >= $2 AND NOT trashed
+                GROUP BY size, md5
+                HAVING COUNT(*) > 1
+            ) d
+            "#,
+            filter.drive_id,
+            filter.min_size
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
@@ -1,9 +1,10 @@
-use crate::database::{Connection, Pool};
+use super::ChangelogKey;
+use crate::database::Connection;
 use futures::prelude::*;
 use sqlx::Result;
 use tracing::trace;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct File {
     pub id: String,
     pub drive_id: String,
@@ -38,6 +39,36 @@ impl File {
         Ok(())
     }
 
+    /// Create several files with a single statement.
+    pub(crate) async fn create_many(files: &[Self], conn: &mut Connection) -> Result<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let sql = format!(
+            "INSERT INTO files (id, drive_id, name, trashed, parent, md5, size) VALUES {}",
+            super::values(files.len(), 7)
+        );
+
+        // Not checked, the number of rows varies.
+        let mut query = sqlx::query(&sql);
+        for file in files {
+            query = query
+                .bind(&file.id)
+                .bind(&file.drive_id)
+                .bind(&file.name)
+                .bind(file.trashed)
+                .bind(&file.parent)
+                .bind(&file.md5)
+                .bind(file.size);
+        }
+
+        query.execute(conn).await?;
+
+        trace!(count = files.len(), "created files");
+        Ok(())
+    }
+
     pub(crate) async fn upsert(&self, conn: &mut Connection) -> Result<()> {
         sqlx::query!(
             "
@@ -67,6 +98,30 @@ impl File {
         Ok(())
     }
 
+    pub(crate) async fn get_by_id(
+        id: &str,
+        drive_id: &str,
+        conn: &mut Connection,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            "SELECT * FROM files WHERE id = $1 AND drive_id = $2",
+            id,
+            drive_id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    pub(crate) async fn count(drive_id: &str, conn: &mut Connection) -> Result<i64> {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!: i64" FROM files WHERE drive_id = $1"#,
+            drive_id
+        )
+        .fetch_one(conn)
+        .await
+    }
+
     pub(crate) async fn delete(id: &str, drive_id: &str, conn: &mut Connection) -> Result<()> {
         sqlx::query!(
             "DELETE FROM files WHERE id = $1 AND drive_id = $2",
@@ -81,7 +136,7 @@ impl File {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ChangedFile {
     Created(File),
     Deleted(File),
@@ -120,32 +175,109 @@ impl From<FileChangelog> for ChangedFile {
         };
 
         match f.deleted {
-            true => Self::Created(file),
-            false => Self::Deleted(file),
+            true => Self::Deleted(file),
+            false => Self::Created(file),
         }
     }
 }
 
 impl ChangedFile {
-    pub(crate) async fn get_all(drive_id: &str, pool: &Pool) -> Result<Vec<Self>> {
+    pub(crate) async fn get_all(drive_id: &str, conn: &mut Connection) -> Result<Vec<Self>> {
         sqlx::query_as!(
             FileChangelog,
             "SELECT * FROM file_changelog WHERE drive_id = $1",
             drive_id
         )
-        .fetch(pool)
+        .fetch(conn)
+        // Turn the FileChangelog into a ChangedFile
+        .map_ok(|f| f.into())
+        .try_collect()
+        .await
+    }
+
+    /// Count the files in the changelog which have been deleted and which have been trashed.
+    pub(crate) async fn count_removed(drive_id: &str, conn: &mut Connection) -> Result<(i64, i64)> {
+        let deleted = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!: i64" FROM file_changelog old
+            WHERE old.drive_id = $1 AND old.deleted AND NOT EXISTS (
+                SELECT * FROM file_changelog new
+                WHERE new.id = old.id AND new.drive_id = old.drive_id AND NOT new.deleted
+            )
+            "#,
+            drive_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let trashed = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!: i64" FROM file_changelog new
+            INNER JOIN file_changelog old
+                ON old.id = new.id AND old.drive_id = new.drive_id AND old.deleted
+            WHERE new.drive_id = $1 AND NOT new.deleted AND new.trashed AND NOT old.trashed
+            "#,
+            drive_id
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        Ok((deleted, trashed))
+    }
+
+    pub(crate) async fn get_page(
+        drive_id: &str,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+        conn: &mut Connection,
+    ) -> Result<Vec<Self>> {
+        let id = after.map(|key| key.id.as_str());
+        let deleted = after.map(|key| key.deleted);
+
+        sqlx::query_as!(
+            FileChangelog,
+            "
+            SELECT * FROM file_changelog
+            WHERE drive_id = $1 AND (CAST($2 AS TEXT) IS NULL OR (id, deleted) > ($2, $3))
+            ORDER BY id, deleted
+            LIMIT $4
+            ",
+            drive_id,
+            id,
+            deleted,
+            limit
+        )
+        .fetch(conn)
         // Turn the FileChangelog into a ChangedFile
         .map_ok(|f| f.into())
         .try_collect()
         .await
     }
 
-    pub(crate) async fn clear(drive_id: &str, pool: &Pool) -> Result<()> {
+    /// The position of this change within the changelog.
+    pub fn key(&self) -> ChangelogKey {
+        match self {
+            Self::Created(item) => ChangelogKey::new(&item.id, false),
+            Self::Deleted(item) => ChangelogKey::new(&item.id, true),
+        }
+    }
+
+    pub(crate) async fn clear(drive_id: &str, conn: &mut Connection) -> Result<()> {
         sqlx::query!("DELETE FROM file_changelog WHERE drive_id = $1", drive_id)
-            .execute(pool)
+            .execute(conn)
             .await?;
 
         trace!("cleared file changelog");
         Ok(())
     }
+
+    /// Clear the changelog of the drives which have been removed.
+    pub(crate) async fn clear_removed_drives(conn: &mut Connection) -> Result<()> {
+        sqlx::query!("DELETE FROM file_changelog WHERE drive_id NOT IN (SELECT id FROM drives)")
+            .execute(conn)
+            .await?;
+
+        trace!("cleared file changelog of removed drives");
+        Ok(())
+    }
 }
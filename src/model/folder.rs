@@ -1,9 +1,10 @@
-use crate::database::{Connection, Pool};
+use super::ChangelogKey;
+use crate::database::Connection;
 use futures::prelude::*;
 use sqlx::Result;
 use tracing::trace;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Folder {
     pub id: String,
     pub drive_id: String,
@@ -34,6 +35,34 @@ impl Folder {
         Ok(())
     }
 
+    /// Create several folders with a single statement.
+    pub(crate) async fn create_many(folders: &[Self], conn: &mut Connection) -> Result<()> {
+        if folders.is_empty() {
+            return Ok(());
+        }
+
+        let sql = format!(
+            "INSERT INTO folders (id, drive_id, name, trashed, parent) VALUES {}",
+            super::values(folders.len(), 5)
+        );
+
+        // Not checked, the number of rows varies.
+        let mut query = sqlx::query(&sql);
+        for folder in folders {
+            query = query
+                .bind(&folder.id)
+                .bind(&folder.drive_id)
+                .bind(&folder.name)
+                .bind(folder.trashed)
+                .bind(&folder.parent);
+        }
+
+        query.execute(conn).await?;
+
+        trace!(count = folders.len(), "created folders");
+        Ok(())
+    }
+
     pub(crate) async fn upsert(&self, conn: &mut Connection) -> Result<()> {
         sqlx::query!(
             "
@@ -59,6 +88,33 @@ impl Folder {
         Ok(())
     }
 
+    pub(crate) async fn get_by_id(
+        id: &str,
+        drive_id: &str,
+        conn: &mut Connection,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            "SELECT * FROM folders WHERE id = $1 AND drive_id = $2",
+            id,
+            drive_id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    pub(crate) async fn exists(id: &str, drive_id: &str, conn: &mut Connection) -> Result<bool> {
+        let folder = sqlx::query!(
+            "SELECT id FROM folders WHERE id = $1 AND drive_id = $2",
+            id,
+            drive_id
+        )
+        .fetch_optional(conn)
+        .await?;
+
+        Ok(folder.is_some())
+    }
+
     pub(crate) async fn delete(id: &str, drive_id: &str, conn: &mut Connection) -> Result<()> {
         sqlx::query!(
             "DELETE FROM folders WHERE id = $1 AND drive_id = $2",
@@ -92,7 +148,7 @@ impl Folder {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ChangedFolder {
     Created(Folder),
     Deleted(Folder),
@@ -127,32 +183,79 @@ impl From<FolderChangelog> for ChangedFolder {
         };
 
         match f.deleted {
-            true => Self::Created(folder),
-            false => Self::Deleted(folder),
+            true => Self::Deleted(folder),
+            false => Self::Created(folder),
         }
     }
 }
 
 impl ChangedFolder {
-    pub(crate) async fn get_all(drive_id: &str, pool: &Pool) -> Result<Vec<Self>> {
+    pub(crate) async fn get_all(drive_id: &str, conn: &mut Connection) -> Result<Vec<Self>> {
         sqlx::query_as!(
             FolderChangelog,
             "SELECT * FROM folder_changelog WHERE drive_id = $1",
             drive_id
         )
-        .fetch(pool)
+        .fetch(conn)
         // Turn the FolderChangelog into a ChangedFolder
         .map_ok(|f| f.into())
         .try_collect()
         .await
     }
 
-    pub(crate) async fn clear(drive_id: &str, pool: &Pool) -> Result<()> {
+    pub(crate) async fn get_page(
+        drive_id: &str,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+        conn: &mut Connection,
+    ) -> Result<Vec<Self>> {
+        let id = after.map(|key| key.id.as_str());
+        let deleted = after.map(|key| key.deleted);
+
+        sqlx::query_as!(
+            FolderChangelog,
+            "
+            SELECT * FROM folder_changelog
+            WHERE drive_id = $1 AND (CAST($2 AS TEXT) IS NULL OR (id, deleted) > ($2, $3))
+            ORDER BY id, deleted
+            LIMIT $4
+            ",
+            drive_id,
+            id,
+            deleted,
+            limit
+        )
+        .fetch(conn)
+        // Turn the FolderChangelog into a ChangedFolder
+        .map_ok(|f| f.into())
+        .try_collect()
+        .await
+    }
+
+    /// The position of this change within the changelog.
+    pub fn key(&self) -> ChangelogKey {
+        match self {
+            Self::Created(item) => ChangelogKey::new(&item.id, false),
+            Self::Deleted(item) => ChangelogKey::new(&item.id, true),
+        }
+    }
+
+    pub(crate) async fn clear(drive_id: &str, conn: &mut Connection) -> Result<()> {
         sqlx::query!("DELETE FROM folder_changelog WHERE drive_id = $1", drive_id)
-            .execute(pool)
+            .execute(conn)
             .await?;
 
         trace!("cleared folder changelog");
         Ok(())
     }
+
+    /// Clear the changelog of the drives which have been removed.
+    pub(crate) async fn clear_removed_drives(conn: &mut Connection) -> Result<()> {
+        sqlx::query!("DELETE FROM folder_changelog WHERE drive_id NOT IN (SELECT id FROM drives)")
+            .execute(conn)
+            .await?;
+
+        trace!("cleared folder changelog of removed drives");
+        Ok(())
+    }
 }
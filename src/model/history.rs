@@ -0,0 +1,132 @@
+use crate::database::Connection;
+use chrono::{DateTime, Utc};
+use sqlx::Result;
+use tracing::trace;
+
+/// A committed synchronisation of a Shared Drive.
+#[derive(Debug)]
+pub struct SyncRun {
+    pub id: i64,
+    pub drive_id: String,
+    pub page_token: String,
+    pub synced_at: DateTime<Utc>,
+}
+
+/// A point in the history of a Shared Drive.
+#[derive(Debug, Clone, Copy)]
+pub enum AsOf {
+    /// The state after the last synchronisation at or before this time.
+    Time(DateTime<Utc>),
+    /// The state after the synchronisation with this id.
+    SyncRun(i64),
+}
+
+impl SyncRun {
+    /// Record a synchronisation and the versions of the items in its changelog.
+    pub(crate) async fn record(
+        drive_id: &str,
+        page_token: &str,
+        synced_at: DateTime<Utc>,
+        conn: &mut Connection,
+    ) -> Result<i64> {
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO sync_runs (drive_id, page_token, synced_at) VALUES ($1, $2, $3)
+            RETURNING id as "id!: i64"
+            "#,
+            drive_id,
+            page_token,
+            synced_at
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        // Close the versions which were updated or deleted.
+        let closed = sqlx::query!(
+            "
+            UPDATE item_history SET valid_to = $2
+            WHERE drive_id = $1 AND valid_to IS NULL AND (
+                id IN (SELECT id FROM folder_changelog WHERE drive_id = $1 AND deleted)
+                OR id IN (SELECT id FROM file_changelog WHERE drive_id = $1 AND deleted)
+            )
+            ",
+            drive_id,
+            id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        let opened = sqlx::query!(
+            "
+            WITH created AS (
+                SELECT c.id, c.drive_id, TRUE as folder, c.name, c.trashed, c.parent,
+                    NULL as md5, NULL as size
+                FROM folder_changelog c WHERE c.drive_id = $1 AND NOT c.deleted
+                UNION ALL
+                SELECT c.id, c.drive_id, FALSE, c.name, c.trashed, c.parent, c.md5, c.size
+                FROM file_changelog c WHERE c.drive_id = $1 AND NOT c.deleted
+            )
+            INSERT INTO item_history
+                (id, drive_id, folder, name, trashed, parent, md5, size, valid_from)
+            SELECT c.id, c.drive_id, c.folder, c.name, c.trashed, c.parent, c.md5, c.size, $2
+            FROM created c
+            ",
+            drive_id,
+            id
+        )
+        .execute(conn)
+        .await?
+        .rows_affected();
+
+        trace!(id, closed, opened, "recorded sync run");
+        Ok(id)
+    }
+
+    /// All synchronisations of a Shared Drive, oldest first.
+    pub(crate) async fn get_all(drive_id: &str, conn: &mut Connection) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            Self,
+            r#"
+            SELECT id as "id!", drive_id, page_token, synced_at as "synced_at: DateTime<Utc>"
+            FROM sync_runs WHERE drive_id = $1 ORDER BY id
+            "#,
+            drive_id
+        )
+        .fetch_all(conn)
+        .await
+    }
+
+    /// The id of the synchronisation which defines the state at the given point,
+    /// or `None` if the history starts afterwards.
+    pub(crate) async fn resolve(
+        drive_id: &str,
+        as_of: AsOf,
+        conn: &mut Connection,
+    ) -> Result<Option<i64>> {
+        match as_of {
+            AsOf::Time(time) => {
+                sqlx::query_scalar!(
+                    r#"
+                    SELECT id as "id!" FROM sync_runs WHERE drive_id = $1 AND synced_at <= $2
+                    ORDER BY synced_at DESC, id DESC
+                    LIMIT 1
+                    "#,
+                    drive_id,
+                    time
+                )
+                .fetch_optional(conn)
+                .await
+            }
+            AsOf::SyncRun(id) => {
+                sqlx::query_scalar!(
+                    r#"SELECT id as "id!" FROM sync_runs WHERE drive_id = $1 AND id = $2"#,
+                    drive_id,
+                    id
+                )
+                .fetch_optional(conn)
+                .await
+            }
+        }
+    }
+}
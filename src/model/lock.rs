@@ -0,0 +1,121 @@
+use crate::database::Connection;
+use chrono::Duration;
+use tracing::trace;
+
+/// A lease on the synchronisation of a Shared Drive, which is visible to every process.
+pub(crate) struct SyncLock;
+
+impl SyncLock {
+    /// Take the lease, or extend it if the holder already has it.
+    ///
+    /// Returns `false` if another holder has a lease which has not expired yet.
+    /// The expiry is computed by the clock of the database, which every process shares,
+    /// rather than by the clocks of the hosts, which may drift apart.
+    pub(crate) async fn acquire(
+        drive_id: &str,
+        holder: &str,
+        lease: Duration,
+        conn: &mut Connection,
+    ) -> sqlx::Result<bool> {
+        let lease_ms = lease.num_milliseconds();
+
+        #[cfg(not(feature = "postgres"))]
+        let result = sqlx::query!(
+            "
+            INSERT INTO sync_locks (drive_id, holder, expires_at)
+            VALUES ($1, $2, CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER) + $3)
+            ON CONFLICT (drive_id) DO UPDATE SET
+                holder = EXCLUDED.holder,
+                expires_at = EXCLUDED.expires_at
+            WHERE sync_locks.holder = EXCLUDED.holder
+                OR sync_locks.expires_at <= CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+            ",
+            drive_id,
+            holder,
+            lease_ms
+        )
+        .execute(conn)
+        .await?;
+
+        #[cfg(feature = "postgres")]
+        let result = sqlx::query!(
+            "
+            INSERT INTO sync_locks (drive_id, holder, expires_at)
+            VALUES ($1, $2, CAST(EXTRACT(EPOCH FROM clock_timestamp()) * 1000 AS BIGINT) + $3)
+            ON CONFLICT (drive_id) DO UPDATE SET
+                holder = EXCLUDED.holder,
+                expires_at = EXCLUDED.expires_at
+            WHERE sync_locks.holder = EXCLUDED.holder
+                OR sync_locks.expires_at <= CAST(EXTRACT(EPOCH FROM clock_timestamp()) * 1000 AS BIGINT)
+            ",
+            drive_id,
+            holder,
+            lease_ms
+        )
+        .execute(conn)
+        .await?;
+
+        let acquired = result.rows_affected() > 0;
+
+        trace!(acquired, "acquired sync lock");
+        Ok(acquired)
+    }
+
+    /// Whether the holder still has a lease which has not expired, without extending it.
+    ///
+    /// The lease stays locked until the end of the transaction,
+    /// so another holder cannot take it over before the transaction commits.
+    pub(crate) async fn hold(
+        drive_id: &str,
+        holder: &str,
+        conn: &mut Connection,
+    ) -> sqlx::Result<bool> {
+        #[cfg(not(feature = "postgres"))]
+        let result = sqlx::query!(
+            "
+            UPDATE sync_locks SET holder = holder
+            WHERE drive_id = $1 AND holder = $2
+                AND expires_at > CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)
+            ",
+            drive_id,
+            holder
+        )
+        .execute(conn)
+        .await?;
+
+        #[cfg(feature = "postgres")]
+        let result = sqlx::query!(
+            "
+            UPDATE sync_locks SET holder = holder
+            WHERE drive_id = $1 AND holder = $2
+                AND expires_at > CAST(EXTRACT(EPOCH FROM clock_timestamp()) * 1000 AS BIGINT)
+            ",
+            drive_id,
+            holder
+        )
+        .execute(conn)
+        .await?;
+
+        let held = result.rows_affected() > 0;
+
+        trace!(held, "checked sync lock");
+        Ok(held)
+    }
+
+    pub(crate) async fn release(
+        drive_id: &str,
+        holder: &str,
+        conn: &mut Connection,
+    ) -> sqlx::Result<()> {
+        sqlx::query!(
+            "DELETE FROM sync_locks WHERE drive_id = $1 AND holder = $2",
+            drive_id,
+            holder
+        )
+        .execute(conn)
+        .await?;
+
+        trace!("released sync lock");
+        Ok(())
+    }
+}
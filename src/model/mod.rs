@@ -1,9 +1,59 @@
+mod collision;
 mod drive;
+mod duplicate;
 mod file;
 mod folder;
+mod history;
+mod lock;
 mod path;
+mod pending;
+mod quarantine;
+mod record;
+mod stats;
+mod tombstone;
 
+pub use collision::Collision;
 pub use drive::Drive;
+pub(crate) use duplicate::DuplicateFilter;
+pub use duplicate::{Duplicate, DuplicateSummary};
 pub use file::{ChangedFile, File};
 pub use folder::{ChangedFolder, Folder};
+pub use history::{AsOf, SyncRun};
+pub(crate) use lock::SyncLock;
 pub use path::{ChangedPath, InnerPath, Path};
+pub use pending::PendingChanges;
+pub use quarantine::Quarantined;
+pub use record::Record;
+pub use stats::FolderStats;
+pub use tombstone::Tombstone;
+
+/// Position within a changelog, which is ordered by id and deleted.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ChangelogKey {
+    pub id: String,
+    pub deleted: bool,
+}
+
+impl ChangelogKey {
+    pub(crate) fn new(id: &str, deleted: bool) -> Self {
+        Self {
+            id: id.to_owned(),
+            deleted,
+        }
+    }
+}
+
+/// The placeholders of a multi-row INSERT, such as `($1, $2), ($3, $4)`.
+fn values(rows: usize, columns: usize) -> String {
+    let rows: Vec<_> = (0..rows)
+        .map(|row| {
+            let columns: Vec<_> = (1..=columns)
+                .map(|column| format!("${}", row * columns + column))
+                .collect();
+
+            format!("({})", columns.join(", "))
+        })
+        .collect();
+
+    rows.join(", ")
+}
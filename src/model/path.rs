@@ -1,9 +1,12 @@
 use std::path::PathBuf;
 
-use crate::database::Pool;
+use super::ChangelogKey;
+use crate::database::Connection;
+use crate::search::Search;
 use futures::prelude::*;
+use tracing::trace;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Path {
     File(InnerPath),
     Folder(InnerPath),
@@ -16,9 +19,23 @@ impl Path {
             Self::Folder(inner) => inner.trashed,
         }
     }
+
+    pub fn id(&self) -> &str {
+        match self {
+            Self::File(inner) => &inner.id,
+            Self::Folder(inner) => &inner.id,
+        }
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        match self {
+            Self::File(inner) => &inner.path,
+            Self::Folder(inner) => &inner.path,
+        }
+    }
 }
 
-#[derive(Debug, sqlx::FromRow)]
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct InnerPath {
     pub id: String,
     pub drive_id: String,
@@ -26,7 +43,7 @@ pub struct InnerPath {
     pub trashed: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ChangedPath {
     Created(Path),
     Deleted(Path),
@@ -60,6 +77,443 @@ impl From<Path> for InnerPath {
 }
 
 #[derive(sqlx::FromRow)]
+struct PathRow {
+    pub id: String,
+    pub drive_id: String,
+    pub path: String,
+    pub folder: bool,
+    pub trashed: bool,
+}
+
+impl From<PathRow> for Path {
+    fn from(p: PathRow) -> Self {
+        let inner_path = InnerPath {
+            id: p.id,
+            drive_id: p.drive_id,
+            path: p.path.into(),
+            trashed: p.trashed,
+        };
+
+        match p.folder {
+            true => Path::Folder(inner_path),
+            false => Path::File(inner_path),
+        }
+    }
+}
+
+impl Path {
+    /// The root folder of a Shared Drive does not have a path.
+    pub(crate) async fn get_by_id(
+        id: &str,
+        drive_id: &str,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Option<Self>> {
+        sqlx::query_as!(
+            PathRow,
+            "
+            SELECT id, drive_id, path, folder, trashed FROM item_paths
+            WHERE id = $1 AND drive_id = $2
+            ",
+            id,
+            drive_id
+        )
+        .fetch_optional(conn)
+        .await
+        .map(|p| p.map(Into::into))
+    }
+
+    pub(crate) async fn get_by_path(
+        drive_id: &str,
+        path: &str,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            PathRow,
+            "
+            SELECT id, drive_id, path, folder, trashed FROM item_paths
+            WHERE drive_id = $1 AND path = $2
+            ",
+            drive_id,
+            path
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    pub(crate) async fn get_children(
+        folder_id: &str,
+        drive_id: &str,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            PathRow,
+            "
+            SELECT id, drive_id, path, folder, trashed FROM item_paths
+            WHERE drive_id = $2 AND id IN (
+                SELECT id FROM folders WHERE parent = $1 AND drive_id = $2
+                UNION ALL
+                SELECT id FROM files WHERE parent = $1 AND drive_id = $2
+            )
+            ",
+            folder_id,
+            drive_id
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    /// All paths starting with `prefix/`, where the root folder has an empty prefix.
+    pub(crate) async fn get_descendants(
+        drive_id: &str,
+        prefix: &str,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        // '0' directly follows '/', so this range contains exactly the paths starting with `prefix/`.
+        let start = format!("{}/", prefix);
+        let end = format!("{}0", prefix);
+
+        sqlx::query_as!(
+            PathRow,
+            "
+            SELECT id, drive_id, path, folder, trashed FROM item_paths
+            WHERE drive_id = $1 AND path >= $2 AND path < $3
+            ",
+            drive_id,
+            start,
+            end
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    /// The paths of all files and folders as they were after a synchronisation,
+    /// optionally limited to a path and everything within it.
+    ///
+    /// The names are disambiguated like the materialised paths if the drive disambiguates them.
+    /// Overlapping versions of a folder could form a cycle,
+    /// hence the paths stop at [`MAX_DEPTH`] levels.
+    pub(crate) async fn get_at(
+        drive_id: &str,
+        sync_run: i64,
+        path: Option<&str>,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        // Not checked, SQLx cannot describe recursive CTEs.
+        sqlx::query_as::<_, PathRow>(
+            r#"
+            WITH RECURSIVE
+            versions AS (
+                SELECT h.*, RTRIM(h.name, REPLACE(h.name, '.', '')) as stem FROM item_history h
+                WHERE drive_id = $1 AND valid_from <= $2 AND (valid_to IS NULL OR valid_to > $2)
+            ),
+            segments(id, folder, trashed, parent, segment) AS (
+                SELECT v.id, v.folder, v.trashed, v.parent, CASE
+                    WHEN NOT d.disambiguate_paths OR NOT EXISTS (
+                        SELECT 1 FROM versions s
+                        WHERE s.parent = v.parent AND s.name = v.name AND s.id <> v.id
+                    ) THEN v.name
+                    WHEN NOT v.folder AND LENGTH(v.stem) > 1
+                        THEN SUBSTR(v.stem, 1, LENGTH(v.stem) - 1) || ' (' || v.id || ').'
+                            || SUBSTR(v.name, LENGTH(v.stem) + 1)
+                    ELSE v.name || ' (' || v.id || ')'
+                END
+                FROM versions v
+                INNER JOIN drives d ON d.id = $1
+            ),
+            resolved(id, folder, trashed, path, depth) AS (
+                SELECT s.id, s.folder, s.trashed, '/' || s.segment, 1 FROM segments s
+                WHERE s.parent = $1
+
+                UNION ALL
+
+                SELECT s.id, s.folder, s.trashed, r.path || '/' || s.segment, r.depth + 1
+                FROM segments s
+                INNER JOIN resolved r ON s.parent = r.id
+                WHERE r.folder AND r.depth < $4
+            )
+            SELECT r.id, $1 as drive_id, r.path, r.folder, r.trashed FROM resolved r
+            WHERE $3 IS NULL OR r.path = $3 OR (r.path >= $3 || '/' AND r.path < $3 || '0')
+            ORDER BY r.path
+            "#,
+        )
+        .bind(drive_id)
+        .bind(sync_run)
+        .bind(path)
+        .bind(MAX_DEPTH)
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    /// The paths of the files with the given md5 checksum and size, which are not trashed.
+    pub(crate) async fn get_by_content(
+        md5: &str,
+        size: i64,
+        drive_id: Option<&str>,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            PathRow,
+            "
+            SELECT p.id, p.drive_id, p.path, p.folder, p.trashed FROM files f
+            INNER JOIN item_paths p ON p.id = f.id AND p.drive_id = f.drive_id
+            WHERE f.md5 = $1 AND f.size = $2 AND (CAST($3 AS TEXT) IS NULL OR f.drive_id = $3)
+                AND NOT f.trashed
+            ORDER BY p.drive_id, p.path
+            ",
+            md5,
+            size,
+            drive_id
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    /// Full-text search over the names and paths.
+    #[cfg(not(feature = "postgres"))]
+    pub(crate) async fn search(
+        search: &Search,
+        drive_id: Option<&str>,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        let query = search.to_fts5();
+
+        sqlx::query_as!(
+            PathRow,
+            "
+            SELECT p.id, p.drive_id, p.path, p.folder, p.trashed FROM item_search s
+            INNER JOIN item_paths p ON p.key = s.rowid
+            WHERE item_search MATCH $1 AND (CAST($2 AS TEXT) IS NULL OR p.drive_id = $2)
+            ORDER BY s.rank
+            ",
+            query,
+            drive_id
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    /// Full-text search over the names and paths.
+    #[cfg(feature = "postgres")]
+    pub(crate) async fn search(
+        search: &Search,
+        drive_id: Option<&str>,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        let query = search.to_tsquery();
+
+        sqlx::query_as!(
+            PathRow,
+            "
+            SELECT p.id, p.drive_id, p.path, p.folder, p.trashed FROM item_paths p
+            WHERE p.search @@ to_tsquery('simple', $1)
+                AND (CAST($2 AS TEXT) IS NULL OR p.drive_id = $2)
+            ORDER BY ts_rank(p.search, to_tsquery('simple', $1)) DESC
+            ",
+            query,
+            drive_id
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    /// Update the materialised paths of all items in the changelog and their descendants,
+    /// or of all items in the drive when rebuilding.
+    /// The replaced paths are kept in `stale_paths` until the changelog has been recorded.
+    ///
+    /// This must be called after all changes have been applied,
+    /// as the path of an item depends on all of its ancestors.
+    /// With `disambiguate`, names shared by siblings are suffixed with their id,
+    /// hence the siblings sharing a name with an item in the changelog are refreshed as well.
+    #[tracing::instrument(level = "debug", skip(conn))]
+    pub(crate) async fn refresh(
+        drive_id: &str,
+        disambiguate: bool,
+        rebuild: bool,
+        conn: &mut Connection,
+    ) -> sqlx::Result<()> {
+        // Not checked, SQLx cannot describe recursive CTEs.
+        let stale = sqlx::query(&format!(
+            "
+            WITH RECURSIVE {}, {}
+            INSERT INTO stale_paths (id, drive_id, folder, trashed, name, path)
+            SELECT p.id, p.drive_id, p.folder, p.trashed, p.name, p.path FROM item_paths p
+            WHERE p.drive_id = $1 AND p.id IN (SELECT id FROM dirty)
+            ",
+            CHANGED_NAMES, DIRTY
+        ))
+        .bind(drive_id)
+        .bind(disambiguate)
+        .bind(rebuild)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        sqlx::query!(
+            "
+            DELETE FROM item_paths
+            WHERE drive_id = $1 AND id IN (SELECT id FROM stale_paths WHERE drive_id = $1)
+            ",
+            drive_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let created = sqlx::query(&format!(
+            "
+            WITH RECURSIVE {}, {}, {}, {}
+            INSERT INTO item_paths (id, drive_id, folder, trashed, name, path)
+            SELECT r.id, $1, r.folder, r.trashed, r.name, r.path FROM resolved r
+            ",
+            CHANGED_NAMES, DIRTY, SEGMENTS, RESOLVED
+        ))
+        .bind(drive_id)
+        .bind(disambiguate)
+        .bind(rebuild)
+        .execute(conn)
+        .await?
+        .rows_affected();
+
+        trace!(stale, created, "refreshed paths");
+        Ok(())
+    }
+}
+
+// The names in the changelog, of which the siblings may have to be disambiguated.
+// Both the old and the new name, as a collision can disappear as well.
+const CHANGED_NAMES: &str = "
+    changed_names(parent, name) AS (
+        SELECT c.parent, c.name FROM folder_changelog c WHERE $2 AND c.drive_id = $1
+        UNION
+        SELECT c.parent, c.name FROM file_changelog c WHERE $2 AND c.drive_id = $1
+    )
+";
+
+// The items in the changelog, their siblings with a changed name and all of their descendants.
+// The root folder is not part of any path, so renaming the drive does not affect them.
+#[cfg(not(feature = "postgres"))]
+const DIRTY: &str = "
+    dirty(id, folder) AS (
+        SELECT c.id, TRUE FROM folder_changelog c WHERE c.drive_id = $1 AND c.id <> $1
+        UNION
+        SELECT c.id, FALSE FROM file_changelog c WHERE c.drive_id = $1
+        UNION
+        SELECT f.id, TRUE FROM folders f WHERE $3 AND f.drive_id = $1 AND f.id <> $1
+        UNION
+        SELECT f.id, FALSE FROM files f WHERE $3 AND f.drive_id = $1
+        UNION
+        SELECT f.id, TRUE FROM folders f
+        INNER JOIN changed_names n ON f.parent = n.parent AND f.drive_id = $1 AND f.name = n.name
+        UNION
+        SELECT f.id, FALSE FROM files f
+        INNER JOIN changed_names n ON f.parent = n.parent AND f.drive_id = $1 AND f.name = n.name
+        UNION
+        SELECT f.id, TRUE FROM folders f
+        INNER JOIN dirty d ON f.parent = d.id AND f.drive_id = $1
+        WHERE d.folder
+        UNION
+        SELECT f.id, FALSE FROM files f
+        INNER JOIN dirty d ON f.parent = d.id AND f.drive_id = $1
+        WHERE d.folder
+    )
+";
+
+// Postgres only allows a single recursive SELECT, hence the folders and files are combined.
+#[cfg(feature = "postgres")]
+const DIRTY: &str = "
+    dirty(id, folder) AS (
+        SELECT c.id, TRUE FROM folder_changelog c WHERE c.drive_id = $1 AND c.id <> $1
+        UNION
+        SELECT c.id, FALSE FROM file_changelog c WHERE c.drive_id = $1
+        UNION
+        SELECT f.id, TRUE FROM folders f WHERE $3 AND f.drive_id = $1 AND f.id <> $1
+        UNION
+        SELECT f.id, FALSE FROM files f WHERE $3 AND f.drive_id = $1
+        UNION
+        SELECT f.id, TRUE FROM folders f
+        INNER JOIN changed_names n ON f.parent = n.parent AND f.drive_id = $1 AND f.name = n.name
+        UNION
+        SELECT f.id, FALSE FROM files f
+        INNER JOIN changed_names n ON f.parent = n.parent AND f.drive_id = $1 AND f.name = n.name
+        UNION
+        SELECT i.id, i.folder FROM (
+            SELECT f.id, TRUE as folder, f.parent FROM folders f WHERE f.drive_id = $1
+            UNION ALL
+            SELECT f.id, FALSE, f.parent FROM files f WHERE f.drive_id = $1
+        ) i
+        INNER JOIN dirty d ON i.parent = d.id
+        WHERE d.folder
+    )
+";
+
+// The name of every dirty item within its path.
+// With disambiguation, a name shared by siblings is suffixed with the id of the item,
+// such as `Season 1 (id)`, or `movie (id).mkv` to keep the extension of a file.
+// The stem is the name up to and including its last dot, if any.
+const SEGMENTS: &str = "
+    segments(id, folder, trashed, parent, name, segment) AS (
+        SELECT i.id, i.folder, i.trashed, i.parent, i.name, CASE
+            WHEN NOT $2 OR (
+                NOT EXISTS (
+                    SELECT 1 FROM folders s
+                    WHERE s.parent = i.parent AND s.drive_id = $1 AND s.name = i.name AND s.id <> i.id
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM files s
+                    WHERE s.parent = i.parent AND s.drive_id = $1 AND s.name = i.name AND s.id <> i.id
+                )
+            ) THEN i.name
+            WHEN NOT i.folder AND LENGTH(i.stem) > 1
+                THEN SUBSTR(i.stem, 1, LENGTH(i.stem) - 1) || ' (' || i.id || ').'
+                    || SUBSTR(i.name, LENGTH(i.stem) + 1)
+            ELSE i.name || ' (' || i.id || ')'
+        END
+        FROM (
+            SELECT f.id, TRUE as folder, f.trashed, f.name, f.parent,
+                RTRIM(f.name, REPLACE(f.name, '.', '')) as stem
+            FROM folders f
+            WHERE f.drive_id = $1 AND f.id IN (SELECT id FROM dirty WHERE folder)
+            UNION ALL
+            SELECT f.id, FALSE, f.trashed, f.name, f.parent,
+                RTRIM(f.name, REPLACE(f.name, '.', ''))
+            FROM files f
+            WHERE f.drive_id = $1 AND f.id IN (SELECT id FROM dirty WHERE NOT folder)
+        ) i
+    )
+";
+
+// The new paths of the dirty items, resolved from the top down.
+const RESOLVED: &str = "
+    resolved(id, folder, trashed, name, path) AS (
+        -- Dirty items of which the parent already has a path, or is the root folder.
+        SELECT s.id, s.folder, s.trashed, s.name, COALESCE(p.path, '') || '/' || s.segment
+        FROM segments s
+        LEFT JOIN item_paths p ON p.id = s.parent AND p.drive_id = $1
+        WHERE p.id IS NOT NULL OR s.parent = $1
+
+        UNION ALL
+
+        -- Dirty items within resolved folders.
+        SELECT s.id, s.folder, s.trashed, s.name, r.path || '/' || s.segment
+        FROM segments s
+        INNER JOIN resolved r ON s.parent = r.id
+        WHERE r.folder
+    )
+";
+
 struct PathChangelog {
     pub id: String,
     pub drive_id: String,
@@ -95,16 +549,165 @@ impl From<PathChangelog> for ChangedPath {
 }
 
 impl ChangedPath {
-    pub(crate) async fn get_all(drive_id: &str, pool: &Pool) -> sqlx::Result<Vec<Self>> {
-        // TODO: SQLx appears to have a bug with Recursive CTEs (even if it's just a view).
-        // Therefore this query is not checked.
-        // Maybe open an issue or investigate what goes wrong?
-        sqlx::query_as::<_, PathChangelog>("SELECT * FROM path_changelog WHERE drive_id = $1")
-            .bind(drive_id)
-            .fetch(pool)
-            // Turn the PathChangelog into a ChangedPath
-            .map_ok(|f| f.into())
-            .try_collect()
-            .await
+    pub(crate) async fn get_page(
+        drive_id: &str,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+        conn: &mut Connection,
+    ) -> sqlx::Result<Vec<Self>> {
+        let id = after.map(|key| key.id.as_str());
+        let deleted = after.map(|key| key.deleted);
+
+        sqlx::query_as!(
+            PathChangelog,
+            r#"
+            SELECT
+                id, drive_id, path, folder as "folder: bool",
+                deleted as "deleted: bool", trashed as "trashed: bool"
+            FROM changelog_paths
+            WHERE drive_id = $1 AND (CAST($2 AS TEXT) IS NULL OR (id, deleted) > ($2, $3))
+            ORDER BY id, deleted
+            LIMIT $4
+            "#,
+            drive_id,
+            id,
+            deleted,
+            limit
+        )
+        .fetch(conn)
+        // Turn the PathChangelog into a ChangedPath
+        .map_ok(|f| f.into())
+        .try_collect()
+        .await
+    }
+
+    /// The position of this change within the changelog.
+    pub fn key(&self) -> ChangelogKey {
+        match self {
+            Self::Created(path) => ChangelogKey::new(path.id(), false),
+            Self::Deleted(path) => ChangelogKey::new(path.id(), true),
+        }
+    }
+
+    pub(crate) async fn get_all(drive_id: &str, conn: &mut Connection) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as!(
+            PathChangelog,
+            r#"
+            SELECT
+                id, drive_id, path, folder as "folder: bool",
+                deleted as "deleted: bool", trashed as "trashed: bool"
+            FROM changelog_paths
+            WHERE drive_id = $1
+            ORDER BY id, deleted
+            "#,
+            drive_id
+        )
+        .fetch(conn)
+        // Turn the PathChangelog into a ChangedPath
+        .map_ok(|f| f.into())
+        .try_collect()
+        .await
+    }
+
+    /// Record the paths of the changelog once the materialised paths are refreshed,
+    /// where a deleted item takes its stale path and a created item its refreshed path.
+    pub(crate) async fn record(drive_id: &str, conn: &mut Connection) -> sqlx::Result<()> {
+        sqlx::query!(
+            "
+            INSERT INTO changelog_paths (id, drive_id, deleted, folder, trashed, path)
+            SELECT s.id, s.drive_id, TRUE, s.folder, s.trashed, s.path FROM stale_paths s
+            WHERE s.drive_id = $1 AND s.id IN (
+                SELECT c.id FROM folder_changelog c WHERE c.drive_id = $1 AND c.deleted = TRUE
+                UNION
+                SELECT c.id FROM file_changelog c WHERE c.drive_id = $1 AND c.deleted = TRUE
+            )
+            ",
+            drive_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO changelog_paths (id, drive_id, deleted, folder, trashed, path)
+            SELECT p.id, p.drive_id, FALSE, p.folder, p.trashed, p.path FROM item_paths p
+            WHERE p.drive_id = $1 AND p.id IN (
+                SELECT c.id FROM folder_changelog c WHERE c.drive_id = $1 AND c.deleted = FALSE
+                UNION
+                SELECT c.id FROM file_changelog c WHERE c.drive_id = $1 AND c.deleted = FALSE
+            )
+            ",
+            drive_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        // Items outside of the changelog of which the name gained or lost the suffix with its id,
+        // as a sibling with the same name appeared or disappeared, or the disambiguation was switched.
+        // Like renaming a folder, this does not list the items within the folder.
+        for deleted in [true, false] {
+            sqlx::query!(
+                "
+                INSERT INTO changelog_paths (id, drive_id, deleted, folder, trashed, path)
+                SELECT p.id, p.drive_id, $2, p.folder, p.trashed, CASE WHEN $2 THEN s.path ELSE p.path END
+                FROM item_paths p
+                INNER JOIN stale_paths s ON s.id = p.id AND s.drive_id = p.drive_id
+                WHERE p.drive_id = $1
+                AND (substr(s.path, length(s.path) - length(s.name)) = '/' || s.name)
+                    <> (substr(p.path, length(p.path) - length(p.name)) = '/' || p.name)
+                AND p.id NOT IN (
+                    SELECT c.id FROM folder_changelog c WHERE c.drive_id = $1
+                    UNION
+                    SELECT c.id FROM file_changelog c WHERE c.drive_id = $1
+                )
+                ",
+                drive_id,
+                deleted
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        sqlx::query!("DELETE FROM stale_paths WHERE drive_id = $1", drive_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Record the current paths of all items, before the drive is removed.
+    pub(crate) async fn record_removed(drive_id: &str, conn: &mut Connection) -> sqlx::Result<()> {
+        sqlx::query!(
+            "
+            INSERT INTO changelog_paths (id, drive_id, deleted, folder, trashed, path)
+            SELECT p.id, p.drive_id, TRUE, p.folder, p.trashed, p.path FROM item_paths p
+            WHERE p.drive_id = $1
+            ",
+            drive_id
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn clear(drive_id: &str, conn: &mut Connection) -> sqlx::Result<()> {
+        sqlx::query!("DELETE FROM changelog_paths WHERE drive_id = $1", drive_id)
+            .execute(conn)
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn clear_removed_drives(conn: &mut Connection) -> sqlx::Result<()> {
+        sqlx::query!("DELETE FROM changelog_paths WHERE drive_id NOT IN (SELECT id FROM drives)")
+            .execute(conn)
+            .await?;
+
+        Ok(())
     }
 }
+
+/// Shared Drives nest folders up to 100 levels deep,
+/// so a deeper path can only be the result of a cycle.
+const MAX_DEPTH: i64 = 128;
@@ -0,0 +1,202 @@
+use super::{File, Folder};
+use crate::database::Connection;
+use crate::fetch::{Change, Item, PartialDrive};
+use futures::prelude::*;
+use sqlx::Result;
+use tracing::trace;
+
+/// A change list which has been rejected by the mass-deletion safeguard.
+///
+/// It is kept until it is accepted, or until a later change list of the drive is merged or rejected.
+#[derive(Debug, Clone)]
+pub struct PendingChanges {
+    pub changes: Vec<Change>,
+    pub page_token: String,
+}
+
+struct PendingRow {
+    id: String,
+    drive: bool,
+    removed: bool,
+    folder: Option<bool>,
+    item_drive_id: Option<String>,
+    name: Option<String>,
+    trashed: Option<bool>,
+    parent: Option<String>,
+    md5: Option<String>,
+    size: Option<i64>,
+}
+
+impl From<PendingRow> for Change {
+    fn from(p: PendingRow) -> Self {
+        let name = p.name.unwrap_or_default();
+
+        match (p.drive, p.removed) {
+            (true, true) => Change::DriveRemoved(p.id),
+            (true, false) => Change::DriveChanged(PartialDrive { id: p.id, name }),
+            (false, true) => Change::ItemRemoved(p.id),
+            (false, false) => {
+                let drive_id = p.item_drive_id.unwrap_or_default();
+                let trashed = p.trashed.unwrap_or_default();
+
+                let item = match (p.folder, p.parent, p.md5, p.size) {
+                    (Some(false), Some(parent), Some(md5), Some(size)) => Item::File(File {
+                        id: p.id,
+                        drive_id,
+                        name,
+                        trashed,
+                        parent,
+                        md5,
+                        size,
+                    }),
+                    (_, parent, _, _) => Item::Folder(Folder {
+                        id: p.id,
+                        drive_id,
+                        name,
+                        trashed,
+                        parent,
+                    }),
+                };
+
+                Change::ItemChanged(item)
+            }
+        }
+    }
+}
+
+impl PendingChanges {
+    pub(crate) async fn get(drive_id: &str, conn: &mut Connection) -> Result<Option<Self>> {
+        let page_token = sqlx::query!(
+            "SELECT pending_page_token FROM drives WHERE id = $1",
+            drive_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .and_then(|row| row.pending_page_token);
+
+        let page_token = match page_token {
+            Some(page_token) => page_token,
+            None => return Ok(None),
+        };
+
+        let changes = sqlx::query_as!(
+            PendingRow,
+            r#"
+            SELECT
+                id, drive as "drive: bool", removed as "removed: bool", folder as "folder: bool",
+                item_drive_id, name, trashed as "trashed: bool", parent, md5, size
+            FROM pending_changes
+            WHERE drive_id = $1
+            ORDER BY position
+            "#,
+            drive_id
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await?;
+
+        Ok(Some(Self {
+            changes,
+            page_token,
+        }))
+    }
+
+    /// Replace the pending change list of a Shared Drive.
+    pub(crate) async fn replace(
+        drive_id: &str,
+        changes: &[Change],
+        page_token: &str,
+        conn: &mut Connection,
+    ) -> Result<()> {
+        Self::clear(drive_id, conn).await?;
+
+        sqlx::query!(
+            "UPDATE drives SET pending_page_token = $2 WHERE id = $1",
+            drive_id,
+            page_token
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        for (position, change) in changes.iter().enumerate() {
+            let position = position as i64;
+            let (id, drive, removed) = match change {
+                Change::DriveChanged(drive) => (&drive.id, true, false),
+                Change::DriveRemoved(id) => (id, true, true),
+                Change::ItemChanged(Item::File(file)) => (&file.id, false, false),
+                Change::ItemChanged(Item::Folder(folder)) => (&folder.id, false, false),
+                Change::ItemRemoved(id) => (id, false, true),
+            };
+
+            let (folder, item_drive_id, name, trashed, parent, md5, size) = match change {
+                Change::DriveChanged(drive) => {
+                    (None, None, Some(&drive.name), None, None, None, None)
+                }
+                Change::ItemChanged(Item::File(file)) => (
+                    Some(false),
+                    Some(&file.drive_id),
+                    Some(&file.name),
+                    Some(file.trashed),
+                    Some(&file.parent),
+                    Some(&file.md5),
+                    Some(file.size),
+                ),
+                Change::ItemChanged(Item::Folder(folder)) => (
+                    Some(true),
+                    Some(&folder.drive_id),
+                    Some(&folder.name),
+                    Some(folder.trashed),
+                    folder.parent.as_ref(),
+                    None,
+                    None,
+                ),
+                Change::DriveRemoved(_) | Change::ItemRemoved(_) => {
+                    (None, None, None, None, None, None, None)
+                }
+            };
+
+            sqlx::query!(
+                "
+                INSERT INTO pending_changes
+                    (drive_id, position, id, drive, removed, folder, item_drive_id, name, trashed, parent, md5, size)
+                VALUES
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                ",
+                drive_id,
+                position,
+                id,
+                drive,
+                removed,
+                folder,
+                item_drive_id,
+                name,
+                trashed,
+                parent,
+                md5,
+                size,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        trace!(count = changes.len(), "recorded pending changes");
+        Ok(())
+    }
+
+    pub(crate) async fn clear(drive_id: &str, conn: &mut Connection) -> Result<()> {
+        sqlx::query!(
+            "UPDATE drives SET pending_page_token = NULL WHERE id = $1",
+            drive_id
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!("DELETE FROM pending_changes WHERE drive_id = $1", drive_id)
+            .execute(conn)
+            .await?;
+
+        trace!("cleared pending changes");
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use super::{File, Folder};
+use crate::database::Connection;
+use crate::fetch::Item;
+use chrono::{DateTime, Utc};
+use futures::prelude::*;
+use sqlx::Result;
+use tracing::trace;
+
+/// A file or folder of which the parent was unknown when its change was merged.
+///
+/// The change is retried on every merge until its parent is known,
+/// in the meantime the stored item, if any, is left as it was.
+#[derive(Debug, Clone)]
+pub struct Quarantined {
+    pub item: Item,
+    pub reason: String,
+    pub quarantined_at: DateTime<Utc>,
+}
+
+struct QuarantineRow {
+    id: String,
+    drive_id: String,
+    folder: bool,
+    name: String,
+    trashed: bool,
+    parent: String,
+    md5: Option<String>,
+    size: Option<i64>,
+    reason: String,
+    quarantined_at: DateTime<Utc>,
+}
+
+impl From<QuarantineRow> for Quarantined {
+    fn from(q: QuarantineRow) -> Self {
+        let item = match (q.folder, q.md5, q.size) {
+            (false, Some(md5), Some(size)) => Item::File(File {
+                id: q.id,
+                drive_id: q.drive_id,
+                name: q.name,
+                trashed: q.trashed,
+                parent: q.parent,
+                md5,
+                size,
+            }),
+            _ => Item::Folder(Folder {
+                id: q.id,
+                drive_id: q.drive_id,
+                name: q.name,
+                trashed: q.trashed,
+                parent: Some(q.parent),
+            }),
+        };
+
+        Self {
+            item,
+            reason: q.reason,
+            quarantined_at: q.quarantined_at,
+        }
+    }
+}
+
+impl Quarantined {
+    /// The quarantined items of a Shared Drive, oldest first.
+    pub(crate) async fn get_all(drive_id: &str, conn: &mut Connection) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            QuarantineRow,
+            r#"
+            SELECT
+                id, drive_id, folder, name, trashed, parent, md5, size, reason,
+                quarantined_at as "quarantined_at: DateTime<Utc>"
+            FROM quarantine
+            WHERE drive_id = $1
+            ORDER BY quarantined_at, id
+            "#,
+            drive_id
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+
+    /// Replace the quarantined items of a Shared Drive.
+    pub(crate) async fn replace_all(
+        drive_id: &str,
+        quarantined: &[Self],
+        conn: &mut Connection,
+    ) -> Result<()> {
+        sqlx::query!("DELETE FROM quarantine WHERE drive_id = $1", drive_id)
+            .execute(&mut *conn)
+            .await?;
+
+        for q in quarantined {
+            let id = q.item.id();
+            let (folder, trashed, name, parent, md5, size) = match &q.item {
+                Item::File(file) => (
+                    false,
+                    file.trashed,
+                    &file.name,
+                    Some(&file.parent),
+                    Some(&file.md5),
+                    Some(file.size),
+                ),
+                Item::Folder(folder) => (
+                    true,
+                    folder.trashed,
+                    &folder.name,
+                    folder.parent.as_ref(),
+                    None,
+                    None,
+                ),
+            };
+
+            sqlx::query!(
+                "
+                INSERT INTO quarantine
+                    (id, drive_id, folder, name, trashed, parent, md5, size, reason, quarantined_at)
+                VALUES
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ",
+                id,
+                drive_id,
+                folder,
+                name,
+                trashed,
+                parent,
+                md5,
+                size,
+                q.reason,
+                q.quarantined_at,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        trace!(count = quarantined.len(), "quarantined items");
+        Ok(())
+    }
+}
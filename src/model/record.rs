@@ -0,0 +1,50 @@
+use crate::database::Connection;
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A file or folder of an export, where the root folder has the path `/`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Record {
+    pub id: String,
+    pub drive_id: String,
+    pub parent: Option<String>,
+    pub name: String,
+    /// Unknown if the item is not reachable from the root folder.
+    pub path: Option<String>,
+    pub folder: bool,
+    pub trashed: bool,
+    pub md5: Option<String>,
+    pub size: Option<i64>,
+}
+
+impl Record {
+    /// All files and folders of a Shared Drive, ordered by path.
+    pub(crate) fn stream<'a>(
+        drive_id: &'a str,
+        conn: &'a mut Connection,
+    ) -> impl Stream<Item = sqlx::Result<Self>> + 'a {
+        // Not checked, the macros cannot describe this compound SELECT.
+        sqlx::query_as::<_, Self>(
+            "
+            SELECT
+                f.id, f.drive_id, f.parent, f.name,
+                CASE WHEN f.parent IS NULL THEN '/' ELSE p.path END as path,
+                TRUE as folder, f.trashed, NULL as md5, NULL as size
+            FROM folders f
+            LEFT JOIN item_paths p ON p.id = f.id AND p.drive_id = f.drive_id
+            WHERE f.drive_id = $1
+
+            UNION ALL
+
+            SELECT f.id, f.drive_id, f.parent, f.name, p.path, FALSE, f.trashed, f.md5, f.size
+            FROM files f
+            LEFT JOIN item_paths p ON p.id = f.id AND p.drive_id = f.drive_id
+            WHERE f.drive_id = $1
+
+            ORDER BY path
+            ",
+        )
+        .bind(drive_id)
+        .fetch(conn)
+    }
+}
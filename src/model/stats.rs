@@ -0,0 +1,118 @@
+use crate::database::Connection;
+use sqlx::Result;
+use tracing::trace;
+
+/// The recursive totals of a folder, which ignore trashed items and everything within them.
+#[derive(Debug)]
+pub struct FolderStats {
+    pub id: String,
+    pub drive_id: String,
+    /// The size of all files in bytes.
+    pub size: i64,
+    pub files: i64,
+    pub folders: i64,
+}
+
+impl FolderStats {
+    pub(crate) async fn get_by_id(
+        id: &str,
+        drive_id: &str,
+        conn: &mut Connection,
+    ) -> Result<Option<Self>> {
+        sqlx::query_as!(
+            Self,
+            "
+            SELECT id, drive_id, size, files, folders FROM folder_stats
+            WHERE id = $1 AND drive_id = $2
+            ",
+            id,
+            drive_id
+        )
+        .fetch_optional(conn)
+        .await
+    }
+
+    /// Recompute the totals of the folders affected by the changelog.
+    ///
+    /// Only the changed folders, the parents of changed items and their ancestors are recomputed,
+    /// from the bottom up so every folder can rely on the totals of its subfolders.
+    pub(crate) async fn refresh(drive_id: &str, conn: &mut Connection) -> Result<()> {
+        // Not checked, SQLx cannot describe recursive CTEs.
+        let dirty = sqlx::query(
+            "
+            WITH RECURSIVE dirty(id) AS (
+                SELECT c.id FROM folder_changelog c WHERE c.drive_id = $1
+                UNION
+                SELECT c.parent FROM folder_changelog c
+                WHERE c.drive_id = $1 AND c.parent IS NOT NULL
+                UNION
+                SELECT c.parent FROM file_changelog c WHERE c.drive_id = $1
+                UNION
+                SELECT f.parent FROM folders f
+                INNER JOIN dirty d ON f.id = d.id AND f.drive_id = $1
+                WHERE f.parent IS NOT NULL
+            )
+            INSERT INTO folder_stats (id, drive_id, size, files, folders, dirty)
+            SELECT f.id, f.drive_id, 0, 0, 0, TRUE FROM folders f
+            WHERE f.drive_id = $1 AND f.id IN (SELECT id FROM dirty)
+            ON CONFLICT (id, drive_id) DO UPDATE SET dirty = TRUE
+            ",
+        )
+        .bind(drive_id)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        // Each pass recomputes the dirty folders without dirty subfolders.
+        let mut passes = 0;
+
+        loop {
+            let updated = sqlx::query!(
+                "
+                UPDATE folder_stats SET
+                    size = (
+                        SELECT COALESCE(SUM(f.size), 0) FROM files f
+                        WHERE f.parent = folder_stats.id AND f.drive_id = $1 AND NOT f.trashed
+                    ) + (
+                        SELECT COALESCE(SUM(s.size), 0) FROM folders f
+                        INNER JOIN folder_stats s ON s.id = f.id AND s.drive_id = $1
+                        WHERE f.parent = folder_stats.id AND f.drive_id = $1 AND NOT f.trashed
+                    ),
+                    files = (
+                        SELECT COUNT(*) FROM files f
+                        WHERE f.parent = folder_stats.id AND f.drive_id = $1 AND NOT f.trashed
+                    ) + (
+                        SELECT COALESCE(SUM(s.files), 0) FROM folders f
+                        INNER JOIN folder_stats s ON s.id = f.id AND s.drive_id = $1
+                        WHERE f.parent = folder_stats.id AND f.drive_id = $1 AND NOT f.trashed
+                    ),
+                    folders = (
+                        SELECT COALESCE(SUM(s.folders + 1), 0) FROM folders f
+                        INNER JOIN folder_stats s ON s.id = f.id AND s.drive_id = $1
+                        WHERE f.parent = folder_stats.id AND f.drive_id = $1 AND NOT f.trashed
+                    ),
+                    dirty = FALSE
+                WHERE drive_id = $1 AND dirty AND NOT EXISTS (
+                    SELECT * FROM folders f
+                    INNER JOIN folder_stats s ON s.id = f.id AND s.drive_id = $1
+                    WHERE f.parent = folder_stats.id AND f.drive_id = $1 AND s.dirty
+                )
+                ",
+                drive_id
+            )
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+            // Nothing left, or a cycle of folders which can never be resolved.
+            if updated == 0 {
+                break;
+            }
+
+            passes += 1;
+        }
+
+        trace!(dirty, passes, "refreshed folder stats");
+        Ok(())
+    }
+}
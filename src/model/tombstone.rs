@@ -0,0 +1,126 @@
+use crate::database::Connection;
+use chrono::{DateTime, Utc};
+use futures::prelude::*;
+use sqlx::Result;
+use std::path::PathBuf;
+use tracing::trace;
+
+/// A file or folder which has been deleted from a Shared Drive.
+#[derive(Debug)]
+pub struct Tombstone {
+    pub id: String,
+    pub drive_id: String,
+    pub folder: bool,
+    pub name: String,
+    pub parent: Option<String>,
+    /// The last known path, if the item was reachable from the root folder.
+    pub path: Option<PathBuf>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+struct TombstoneRow {
+    id: String,
+    drive_id: String,
+    folder: bool,
+    name: String,
+    parent: Option<String>,
+    path: Option<String>,
+    deleted_at: DateTime<Utc>,
+}
+
+impl From<TombstoneRow> for Tombstone {
+    fn from(t: TombstoneRow) -> Self {
+        Self {
+            id: t.id,
+            drive_id: t.drive_id,
+            folder: t.folder,
+            name: t.name,
+            parent: t.parent,
+            path: t.path.map(Into::into),
+            deleted_at: t.deleted_at,
+        }
+    }
+}
+
+impl Tombstone {
+    /// Record the items which the changelog deleted without creating them again.
+    ///
+    /// Must be called before the paths are refreshed, as the tombstones take the last known path.
+    pub(crate) async fn record(
+        drive_id: &str,
+        deleted_at: DateTime<Utc>,
+        conn: &mut Connection,
+    ) -> Result<()> {
+        let recorded = sqlx::query!(
+            "
+            WITH removed AS (
+                SELECT c.id, c.drive_id, TRUE as folder, c.name, c.parent, p.path
+                FROM folder_changelog c
+                LEFT JOIN item_paths p ON p.id = c.id AND p.drive_id = c.drive_id
+                WHERE c.drive_id = $1 AND c.deleted AND NOT EXISTS (
+                    SELECT * FROM folders f WHERE f.id = c.id AND f.drive_id = c.drive_id
+                )
+                UNION ALL
+                SELECT c.id, c.drive_id, FALSE, c.name, c.parent, p.path
+                FROM file_changelog c
+                LEFT JOIN item_paths p ON p.id = c.id AND p.drive_id = c.drive_id
+                WHERE c.drive_id = $1 AND c.deleted AND NOT EXISTS (
+                    SELECT * FROM files f WHERE f.id = c.id AND f.drive_id = c.drive_id
+                )
+            )
+            INSERT INTO tombstones (id, drive_id, folder, name, parent, path, deleted_at)
+            SELECT r.id, r.drive_id, r.folder, r.name, r.parent, r.path, $2 FROM removed r
+            ",
+            drive_id,
+            deleted_at
+        )
+        .execute(conn)
+        .await?
+        .rows_affected();
+
+        trace!(recorded, "recorded tombstones");
+        Ok(())
+    }
+
+    /// Remove the tombstones of items deleted before the given time.
+    pub(crate) async fn prune(
+        drive_id: &str,
+        before: DateTime<Utc>,
+        conn: &mut Connection,
+    ) -> Result<()> {
+        let pruned = sqlx::query!(
+            "DELETE FROM tombstones WHERE drive_id = $1 AND deleted_at < $2",
+            drive_id,
+            before
+        )
+        .execute(conn)
+        .await?
+        .rows_affected();
+
+        trace!(pruned, "pruned tombstones");
+        Ok(())
+    }
+
+    /// The items deleted since the given time, most recent first.
+    pub(crate) async fn get_since(
+        drive_id: &str,
+        since: DateTime<Utc>,
+        conn: &mut Connection,
+    ) -> Result<Vec<Self>> {
+        sqlx::query_as!(
+            TombstoneRow,
+            r#"
+            SELECT id, drive_id, folder, name, parent, path, deleted_at as "deleted_at: DateTime<Utc>"
+            FROM tombstones
+            WHERE drive_id = $1 AND deleted_at >= $2
+            ORDER BY deleted_at DESC, path
+            "#,
+            drive_id,
+            since
+        )
+        .fetch(conn)
+        .map_ok(Into::into)
+        .try_collect()
+        .await
+    }
+}
@@ -0,0 +1,341 @@
+use crate::database::{Connection, Pool};
+use crate::glob::Glob;
+use crate::model::DuplicateFilter;
+use crate::search::Search;
+use crate::{
+    AsOf, Collision, Duplicate, DuplicateSummary, File, Folder, FolderStats, InnerPath, Path,
+    Quarantined, Result, SyncRun, Tombstone,
+};
+use chrono::{DateTime, Utc};
+use std::iter;
+use std::path::Component;
+
+/// Read-only queries over the synchronised Shared Drives.
+pub struct Query<'a> {
+    pool: &'a Pool,
+}
+
+impl<'a> Query<'a> {
+    pub(crate) fn new(pool: &'a Pool) -> Self {
+        Self { pool }
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn get_file(&self, drive_id: &str, id: &str) -> Result<Option<File>> {
+        let mut conn = self.pool.acquire().await?;
+        let file = File::get_by_id(id, drive_id, &mut conn).await?;
+
+        Ok(file)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn get_folder(&self, drive_id: &str, id: &str) -> Result<Option<Folder>> {
+        let mut conn = self.pool.acquire().await?;
+        let folder = Folder::get_by_id(id, drive_id, &mut conn).await?;
+
+        Ok(folder)
+    }
+
+    /// The path of a file or folder, or `None` for the root folder of a Shared Drive.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn resolve_path(&self, drive_id: &str, id: &str) -> Result<Option<Path>> {
+        let mut conn = self.pool.acquire().await?;
+        let path = Path::get_by_id(id, drive_id, &mut conn).await?;
+
+        Ok(path)
+    }
+
+    /// The recursive size, file count and folder count of a folder.
+    ///
+    /// The root folder of a Shared Drive shares its id with the drive.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn folder_stats(
+        &self,
+        drive_id: &str,
+        folder_id: &str,
+    ) -> Result<Option<FolderStats>> {
+        let mut conn = self.pool.acquire().await?;
+        let stats = FolderStats::get_by_id(folder_id, drive_id, &mut conn).await?;
+
+        Ok(stats)
+    }
+
+    /// The files and folders directly within a folder.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn list_children(&self, drive_id: &str, folder_id: &str) -> Result<Vec<Path>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let children = match Folder::exists(folder_id, drive_id, &mut conn).await? {
+            true => Path::get_children(folder_id, drive_id, &mut conn).await?,
+            false => Vec::new(),
+        };
+
+        Ok(children)
+    }
+
+    /// All files and folders within a folder and its subfolders.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn walk(&self, drive_id: &str, folder_id: &str) -> Result<Vec<Path>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let descendants = match folder_path(folder_id, drive_id, &mut conn).await? {
+            Some(path) => Path::get_descendants(drive_id, &path, &mut conn).await?,
+            None => Vec::new(),
+        };
+
+        Ok(descendants)
+    }
+
+    /// Find the files and folders at a path such as `/Movies/movie.mkv`.
+    ///
+    /// Google Drive allows multiple items with the same name in a folder,
+    /// hence a path can resolve to more than one item.
+    #[tracing::instrument(level = "trace", skip(self, path), fields(path = ?path.as_ref()))]
+    pub async fn lookup_path<P: AsRef<std::path::Path>>(
+        &self,
+        drive_id: &str,
+        path: P,
+    ) -> Result<Vec<Path>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let names = path
+            .as_ref()
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            });
+
+        let path: String = names
+            .flat_map(|name| iter::once("/").chain(iter::once(name)))
+            .collect();
+        let paths = Path::get_by_path(drive_id, &path, &mut conn).await?;
+
+        Ok(paths)
+    }
+
+    /// The names shared by files and folders within the same folder,
+    /// whose paths are identical unless [`BernardBuilder::disambiguate_paths`] is used.
+    ///
+    /// [`BernardBuilder::disambiguate_paths`]: crate::BernardBuilder::disambiguate_paths
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn name_collisions(&self, drive_id: &str) -> Result<Vec<Collision>> {
+        let mut conn = self.pool.acquire().await?;
+        let collisions = Collision::get_all(drive_id, &mut conn).await?;
+
+        Ok(collisions)
+    }
+
+    /// Search the names and paths of files and folders, optionally within a single Shared Drive.
+    ///
+    /// Every term of the query must match, where a term is a word or a `"quoted phrase"`,
+    /// such as `"the office" s01`. A trailing `*` matches prefixes, such as `offi*`,
+    /// and a leading `name:` ignores the path, such as `name:office`.
+    /// Words are runs of letters and digits, hence `x-men` searches for the phrase `"x men"`.
+    /// The syntax is the same for SQLite and PostgreSQL.
+    /// Results are ordered by relevance.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn search(&self, query: &str, drive_id: Option<&str>) -> Result<Vec<Path>> {
+        let search = Search::new(query);
+        if search.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let paths = Path::search(&search, drive_id, &mut conn).await?;
+
+        Ok(paths)
+    }
+
+    /// Find the files and folders matching a glob pattern such as `/TV/**/*.mkv`.
+    ///
+    /// `*` and `?` match within a single segment of the path,
+    /// whereas `**` matches any number of segments.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn glob(&self, drive_id: &str, pattern: &str) -> Result<Vec<Path>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let glob = Glob::new(pattern);
+        let prefix = glob.prefix();
+
+        // Only the paths starting with the literal prefix can match.
+        let paths = match glob.is_literal() {
+            true => Path::get_by_path(drive_id, &prefix, &mut conn).await?,
+            false => Path::get_descendants(drive_id, &prefix, &mut conn).await?,
+        };
+
+        let paths = paths
+            .into_iter()
+            .filter(|path| glob.is_match(&path.path().to_string_lossy()))
+            .collect();
+
+        Ok(paths)
+    }
+
+    /// The files and folders deleted from a Shared Drive since the given time, most recent first.
+    ///
+    /// Tombstones are only kept for the retention configured with [`BernardBuilder::tombstone_retention`].
+    ///
+    /// [`BernardBuilder::tombstone_retention`]: crate::BernardBuilder::tombstone_retention
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn recently_deleted(
+        &self,
+        drive_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Tombstone>> {
+        let mut conn = self.pool.acquire().await?;
+        let tombstones = Tombstone::get_since(drive_id, since, &mut conn).await?;
+
+        Ok(tombstones)
+    }
+
+    /// The files and folders of a Shared Drive which are waiting for their parent, oldest first.
+    ///
+    /// Items are only quarantined with [`BernardBuilder::quarantine_unknown_parents`].
+    ///
+    /// [`BernardBuilder::quarantine_unknown_parents`]: crate::BernardBuilder::quarantine_unknown_parents
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn quarantined(&self, drive_id: &str) -> Result<Vec<Quarantined>> {
+        let mut conn = self.pool.acquire().await?;
+        let quarantined = Quarantined::get_all(drive_id, &mut conn).await?;
+
+        Ok(quarantined)
+    }
+
+    /// The committed synchronisations of a Shared Drive, oldest first.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn sync_runs(&self, drive_id: &str) -> Result<Vec<SyncRun>> {
+        let mut conn = self.pool.acquire().await?;
+        let sync_runs = SyncRun::get_all(drive_id, &mut conn).await?;
+
+        Ok(sync_runs)
+    }
+
+    /// Rebuild the files and folders of a Shared Drive as they were at a point in its history.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn tree_at(&self, drive_id: &str, as_of: AsOf) -> Result<Vec<Path>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let paths = match SyncRun::resolve(drive_id, as_of, &mut conn).await? {
+            Some(sync_run) => Path::get_at(drive_id, sync_run, None, &mut conn).await?,
+            None => Vec::new(),
+        };
+
+        Ok(paths)
+    }
+
+    /// Rebuild a path such as `/Movies` and everything within it
+    /// as it was at a point in the history of a Shared Drive.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn walk_at(&self, drive_id: &str, path: &str, as_of: AsOf) -> Result<Vec<Path>> {
+        let mut conn = self.pool.acquire().await?;
+
+        let paths = match SyncRun::resolve(drive_id, as_of, &mut conn).await? {
+            Some(sync_run) => {
+                let path = path.trim_end_matches('/');
+                Path::get_at(drive_id, sync_run, Some(path), &mut conn).await?
+            }
+            None => Vec::new(),
+        };
+
+        Ok(paths)
+    }
+
+    /// Find files with the same md5 checksum and size.
+    ///
+    /// Trashed files are left out of the groups and the summary,
+    /// as they are deleted once the trash is emptied.
+    pub fn duplicates(&self) -> Duplicates<'a> {
+        Duplicates {
+            pool: self.pool,
+            drive_id: None,
+            min_size: 0,
+            limit: 100,
+            after: None,
+        }
+    }
+}
+
+/// A query for duplicate files, created by [`Query::duplicates`].
+pub struct Duplicates<'a> {
+    pool: &'a Pool,
+    drive_id: Option<String>,
+    min_size: i64,
+    limit: i64,
+    after: Option<(i64, String)>,
+}
+
+impl<'a> Duplicates<'a> {
+    /// Only consider files within a single Shared Drive.
+    pub fn drive(mut self, drive_id: &str) -> Self {
+        self.drive_id = Some(drive_id.to_owned());
+        self
+    }
+
+    /// Ignore files smaller than the given amount of bytes.
+    pub fn min_size(mut self, bytes: i64) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// The maximum amount of groups per page, defaults to 100.
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Continue with the groups following the last group of a previous page.
+    pub fn after(mut self, duplicate: &Duplicate) -> Self {
+        self.after = Some((duplicate.size, duplicate.md5.clone()));
+        self
+    }
+
+    fn filter(&self) -> DuplicateFilter<'_> {
+        DuplicateFilter {
+            drive_id: self.drive_id.as_deref(),
+            min_size: self.min_size,
+            after: self.after.as_ref().map(|(size, md5)| (*size, md5.as_str())),
+            limit: self.limit,
+        }
+    }
+
+    /// A page of duplicate files, ordered by descending size.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn fetch(&self) -> Result<Vec<Duplicate>> {
+        let mut conn = self.pool.acquire().await?;
+        let duplicates = Duplicate::get_page(&self.filter(), &mut conn).await?;
+
+        Ok(duplicates)
+    }
+
+    /// The totals over all duplicate files, regardless of the page.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn summary(&self) -> Result<DuplicateSummary> {
+        let mut conn = self.pool.acquire().await?;
+        let summary = DuplicateSummary::get(&self.filter(), &mut conn).await?;
+
+        Ok(summary)
+    }
+}
+
+/// The path of a folder, where the root folder has an empty path.
+async fn folder_path(
+    folder_id: &str,
+    drive_id: &str,
+    conn: &mut Connection,
+) -> Result<Option<String>> {
+    let folder = match Folder::get_by_id(folder_id, drive_id, conn).await? {
+        Some(folder) => folder,
+        None => return Ok(None),
+    };
+
+    if folder.parent.is_none() {
+        return Ok(Some(String::new()));
+    }
+
+    let path = Path::get_by_id(folder_id, drive_id, conn)
+        .await?
+        .map(|path| InnerPath::from(path).path.to_string_lossy().into_owned());
+
+    Ok(path)
+}
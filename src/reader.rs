@@ -0,0 +1,69 @@
+use crate::database::{self, DatabaseStore};
+use crate::store::Store;
+use crate::{export, Changes, ExportFormat, Path, Query, Result, UnknownDrive};
+use snafu::OptionExt;
+
+/// Read-only access to the database of a [`Bernard`] running elsewhere, without any credentials.
+///
+/// The database is never modified, so any number of readers can run next to the single writer,
+/// each observing the state of the last committed synchronisation.
+///
+/// [`Bernard`]: crate::Bernard
+pub struct BernardReader {
+    store: DatabaseStore,
+}
+
+impl BernardReader {
+    /// Open an existing database, such as the path to a SQLite file in WAL mode.
+    pub async fn open<S: AsRef<str>>(database_path: S) -> Result<Self> {
+        let pool = database::open_read_only(database_path.as_ref()).await?;
+
+        Ok(Self {
+            store: DatabaseStore {
+                pool,
+                disambiguate_paths: false,
+            },
+        })
+    }
+
+    pub async fn close(self) {
+        self.store.close().await
+    }
+
+    /// Query the files and folders stored in the database.
+    pub fn query(&self) -> Query<'_> {
+        Query::new(&self.store.pool)
+    }
+
+    /// Search the names and paths of files and folders, see [`Query::search`].
+    pub async fn search(&self, query: &str, drive_id: Option<&str>) -> Result<Vec<Path>> {
+        self.query().search(query, drive_id).await
+    }
+
+    /// Write every file and folder of a Shared Drive, see [`Bernard::export`].
+    ///
+    /// [`Bernard::export`]: crate::Bernard::export
+    pub async fn export<W>(&self, drive_id: &str, format: ExportFormat, writer: W) -> Result<()>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        export::export(drive_id, format, writer, &self.store.pool).await
+    }
+
+    /// The changes of the most recent synchronisation of a Shared Drive.
+    ///
+    /// The changelog is replaced at once by every synchronisation of the writer,
+    /// so the `Changes` might already be superseded by a later synchronisation.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn changes<'a>(&self, drive_id: &'a str) -> Result<Changes<'a>> {
+        self.store
+            .get_drive(drive_id)
+            .await?
+            .context(UnknownDrive { drive_id })?;
+
+        let changelog = self.store.changelog(drive_id).await?;
+
+        // The synchronisations of the writer are not blocked by a reader.
+        Ok(Changes::new(drive_id, changelog, None))
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// Thresholds for the amount of files a single synchronisation may delete or trash.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Safeguard {
+    pub count: Option<u64>,
+    pub percentage: Option<f64>,
+}
+
+impl Safeguard {
+    pub fn is_exceeded(&self, summary: &DeletionSummary) -> bool {
+        let count = matches!(self.count, Some(count) if summary.removed() > count);
+        let percentage = matches!(self.percentage, Some(p) if summary.percentage() > p);
+
+        count || percentage
+    }
+}
+
+/// The files a rejected synchronisation would have deleted or trashed.
+#[derive(Debug, Clone)]
+pub struct DeletionSummary {
+    pub drive_id: String,
+    /// The amount of files in the Shared Drive before the synchronisation.
+    pub files: u64,
+    pub deleted: u64,
+    pub trashed: u64,
+}
+
+impl DeletionSummary {
+    pub fn removed(&self) -> u64 {
+        self.deleted + self.trashed
+    }
+
+    pub fn percentage(&self) -> f64 {
+        match self.files {
+            0 => 0.0,
+            files => self.removed() as f64 / files as f64 * 100.0,
+        }
+    }
+}
+
+impl fmt::Display for DeletionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} deleted and {} trashed out of {} files ({:.1}%)",
+            self.deleted,
+            self.trashed,
+            self.files,
+            self.percentage()
+        )
+    }
+}
@@ -0,0 +1,110 @@
+/// A full-text search query such as `name:"the office" offi*`.
+///
+/// Every term of the query must match, where a term is a word or a `"quoted phrase"`.
+/// A trailing `*` matches any word starting with the term, and a leading `name:` ignores the path.
+/// Words are runs of letters and digits, hence `x-men` is the phrase `"x men"`.
+#[derive(Debug)]
+pub(crate) struct Search {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug)]
+struct Term {
+    words: Vec<String>,
+    name_only: bool,
+    prefix: bool,
+}
+
+impl Search {
+    pub(crate) fn new(query: &str) -> Self {
+        let mut terms = Vec::new();
+        let mut rest = query.trim_start();
+
+        while !rest.is_empty() {
+            let name_only = rest.starts_with("name:");
+            if name_only {
+                rest = &rest["name:".len()..];
+            }
+
+            let (text, remainder) = match rest.strip_prefix('"') {
+                Some(quoted) => match quoted.find('"') {
+                    Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                    None => (quoted, ""),
+                },
+                None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+            };
+
+            let (prefix, remainder) = match remainder.strip_prefix('*') {
+                Some(remainder) => (true, remainder),
+                None => (text.ends_with('*'), remainder),
+            };
+
+            let words: Vec<_> = text
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .map(str::to_lowercase)
+                .collect();
+
+            if !words.is_empty() {
+                terms.push(Term {
+                    words,
+                    name_only,
+                    prefix,
+                });
+            }
+
+            rest = remainder.trim_start();
+        }
+
+        Self { terms }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The query in the [FTS5 syntax](https://www.sqlite.org/fts5.html#full_text_query_syntax).
+    #[cfg(not(feature = "postgres"))]
+    pub(crate) fn to_fts5(&self) -> String {
+        let terms: Vec<_> = self
+            .terms
+            .iter()
+            .map(|term| {
+                let column = if term.name_only { "name:" } else { "" };
+                let prefix = if term.prefix { "*" } else { "" };
+                format!("{}\"{}\"{}", column, term.words.join(" "), prefix)
+            })
+            .collect();
+
+        terms.join(" ")
+    }
+
+    /// The query in the syntax of `to_tsquery`, where the names have weight `A`.
+    #[cfg(feature = "postgres")]
+    pub(crate) fn to_tsquery(&self) -> String {
+        let terms: Vec<_> = self
+            .terms
+            .iter()
+            .map(|term| {
+                let last = term.words.len() - 1;
+                let words: Vec<_> = term
+                    .words
+                    .iter()
+                    .enumerate()
+                    .map(|(i, word)| {
+                        let prefix = if term.prefix && i == last { "*" } else { "" };
+                        let weight = if term.name_only { "A" } else { "" };
+                        match (prefix, weight) {
+                            ("", "") => word.clone(),
+                            _ => format!("{}:{}{}", word, prefix, weight),
+                        }
+                    })
+                    .collect();
+
+                format!("({})", words.join(" <-> "))
+            })
+            .collect();
+
+        terms.join(" & ")
+    }
+}
@@ -0,0 +1,785 @@
+use super::quarantine::{self, Partition};
+use super::{Changelog, Merge, MergeOptions, Store};
+use crate::fetch::{Change, Item};
+use crate::model::{ChangelogKey, Drive};
+use crate::{
+    ChangedFile, ChangedFolder, ChangedPath, DeletionSummary, DriveExists, File, Folder, InnerPath,
+    MissingParent, Path, PendingChanges, Preview, Quarantined, Result, SyncInProgress,
+    UnknownDrive,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use snafu::OptionExt;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::Mutex;
+use tracing::trace;
+
+/// A store which keeps the Shared Drives in memory, so they are gone once it is dropped.
+///
+/// Only the synchronisation itself is supported,
+/// as the queries, exports and the history of a Shared Drive require a database.
+#[derive(Default)]
+pub struct MemoryStore {
+    drives: Mutex<HashMap<String, MemoryDrive>>,
+    /// The holder and the expiry of the lease per drive.
+    leases: Mutex<HashMap<String, (String, DateTime<Utc>)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// The items and the changelog of a Shared Drive, keyed by id like the tables of the database.
+struct MemoryDrive {
+    id: String,
+    page_token: String,
+    folders: BTreeMap<String, Folder>,
+    files: BTreeMap<String, File>,
+    /// The ids of the items within each folder, so deleting a folder does not scan the drive.
+    children: HashMap<String, BTreeSet<String>>,
+    folder_changelog: BTreeMap<ChangelogKey, Folder>,
+    file_changelog: BTreeMap<ChangelogKey, File>,
+    path_changelog: BTreeMap<ChangelogKey, ChangedPath>,
+    quarantine: BTreeMap<String, Quarantined>,
+    pending: Option<PendingChanges>,
+    /// A removed drive is only kept for the changelog of its removal.
+    removed: bool,
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    type Changelog = MemoryChangelog;
+
+    async fn get_drive(&self, drive_id: &str) -> Result<Option<Drive>> {
+        let drives = self.drives.lock().unwrap();
+
+        let drive = drives.get(drive_id).filter(|drive| !drive.removed);
+
+        Ok(drive.map(|drive| Drive {
+            id: drive.id.clone(),
+            page_token: drive.page_token.clone(),
+        }))
+    }
+
+    async fn add_drive(
+        &self,
+        drive_id: &str,
+        name: &str,
+        page_token: &str,
+        items: Vec<Item>,
+    ) -> Result<()> {
+        let mut drives = self.drives.lock().unwrap();
+
+        if matches!(drives.get(drive_id), Some(drive) if !drive.removed) {
+            return Err(DriveExists { drive_id }.build().into());
+        }
+
+        let mut drive = MemoryDrive::new(drive_id, page_token);
+
+        drive.upsert_folder(Folder {
+            id: drive_id.to_owned(),
+            drive_id: drive_id.to_owned(),
+            name: name.to_owned(),
+            parent: None,
+            trashed: false,
+        });
+
+        for item in items {
+            match item {
+                Item::File(file) => drive.upsert_file(file),
+                Item::Folder(folder) => drive.upsert_folder(folder),
+            }
+        }
+
+        drive.check_parents()?;
+        drive.record_paths(&HashMap::new());
+        drives.insert(drive_id.to_owned(), drive);
+
+        Ok(())
+    }
+
+    async fn merge_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        page_token: &str,
+        options: &MergeOptions<'_>,
+    ) -> Result<Merge> {
+        let mut drives = self.drives.lock().unwrap();
+
+        // The leases stay locked until the merge is done, so no other holder can take one over.
+        let leases = self.leases.lock().unwrap();
+
+        if let Some(holder) = options.holder {
+            let held = matches!(
+                leases.get(drive_id),
+                Some((current, expires_at)) if current == holder && *expires_at > Utc::now()
+            );
+
+            if !held {
+                return Err(SyncInProgress { drive_id }.build().into());
+            }
+        }
+        let drive = drives
+            .get_mut(drive_id)
+            .filter(|drive| !drive.removed)
+            .context(UnknownDrive { drive_id })?;
+
+        let files = drive.files.len();
+
+        // Changes are applied in place, and rolled back unless they are committed.
+        let savepoint = drive.apply(changes, options.quarantine);
+        drive.page_token = page_token.to_owned();
+
+        if let Some(safeguard) = options.safeguard {
+            let (deleted, trashed) = drive.count_removed();
+
+            let summary = DeletionSummary {
+                drive_id: drive_id.to_owned(),
+                files: files as u64,
+                deleted,
+                trashed,
+            };
+
+            if safeguard.is_exceeded(&summary) {
+                drive.rollback(savepoint);
+                return Ok(Merge::Rejected(summary));
+            }
+        }
+
+        if let Err(error) = drive.check_parents() {
+            drive.rollback(savepoint);
+            return Err(error);
+        }
+
+        Ok(Merge::Committed)
+    }
+
+    async fn remove_drive(&self, drive_id: &str, emit_changes: bool) -> Result<()> {
+        let mut drives = self.drives.lock().unwrap();
+        drives.retain(|_, drive| !drive.removed);
+
+        if !emit_changes {
+            drives.remove(drive_id);
+            return Ok(());
+        }
+
+        if let Some(drive) = drives.get_mut(drive_id) {
+            drive.clear_changelog();
+
+            let ids: Vec<String> = drive
+                .folders
+                .keys()
+                .chain(drive.files.keys())
+                .cloned()
+                .collect();
+            let previous = drive.paths(ids.iter());
+
+            for id in ids {
+                drive.delete_file_or_folder(&id);
+            }
+
+            drive.record_paths(&previous);
+
+            drive.pending = None;
+            drive.removed = true;
+        }
+
+        Ok(())
+    }
+
+    async fn preview_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        quarantine: bool,
+    ) -> Result<Preview> {
+        let mut drives = self.drives.lock().unwrap();
+        let drive = drives
+            .get_mut(drive_id)
+            .filter(|drive| !drive.removed)
+            .context(UnknownDrive { drive_id })?;
+
+        // Only the changelog of this preview should be visible, and only while it is taken.
+        let savepoint = drive.apply(changes, quarantine);
+
+        let preview = Preview {
+            paths: drive.changed_paths(),
+            files: drive.changed_files(),
+            folders: drive.changed_folders(),
+        };
+
+        drive.rollback(savepoint);
+
+        Ok(preview)
+    }
+
+    async fn clear_changelog(&self, drive_id: &str) -> Result<()> {
+        let mut drives = self.drives.lock().unwrap();
+
+        if let Some(drive) = drives.get_mut(drive_id) {
+            drive.clear_changelog();
+        }
+
+        Ok(())
+    }
+
+    async fn save_pending_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        page_token: &str,
+    ) -> Result<()> {
+        let mut drives = self.drives.lock().unwrap();
+        let drive = drives
+            .get_mut(drive_id)
+            .filter(|drive| !drive.removed)
+            .context(UnknownDrive { drive_id })?;
+
+        drive.pending = Some(PendingChanges {
+            changes: changes.to_vec(),
+            page_token: page_token.to_owned(),
+        });
+
+        Ok(())
+    }
+
+    async fn pending_changes(&self, drive_id: &str) -> Result<Option<PendingChanges>> {
+        let drives = self.drives.lock().unwrap();
+
+        Ok(drives.get(drive_id).and_then(|drive| drive.pending.clone()))
+    }
+
+    async fn clear_pending_changes(&self, drive_id: &str) -> Result<()> {
+        let mut drives = self.drives.lock().unwrap();
+
+        if let Some(drive) = drives.get_mut(drive_id) {
+            drive.pending = None;
+        }
+
+        Ok(())
+    }
+
+    async fn changelog(&self, drive_id: &str) -> Result<MemoryChangelog> {
+        let drives = self.drives.lock().unwrap();
+
+        let changelog = match drives.get(drive_id) {
+            Some(drive) => MemoryChangelog {
+                paths: drive.changed_paths(),
+                files: drive.changed_files(),
+                folders: drive.changed_folders(),
+            },
+            None => MemoryChangelog::default(),
+        };
+
+        Ok(changelog)
+    }
+
+    async fn acquire_lease(&self, drive_id: &str, holder: &str, lease: Duration) -> Result<bool> {
+        let mut leases = self.leases.lock().unwrap();
+        let now = Utc::now();
+
+        let acquired = match leases.get(drive_id) {
+            Some((current, expires_at)) => current == holder || *expires_at <= now,
+            None => true,
+        };
+
+        if acquired {
+            leases.insert(drive_id.to_owned(), (holder.to_owned(), now + lease));
+        }
+
+        Ok(acquired)
+    }
+
+    async fn release_lease(&self, drive_id: &str, holder: &str) -> Result<()> {
+        let mut leases = self.leases.lock().unwrap();
+
+        if matches!(leases.get(drive_id), Some((current, _)) if current == holder) {
+            leases.remove(drive_id);
+        }
+
+        Ok(())
+    }
+}
+
+impl MemoryDrive {
+    fn new(id: &str, page_token: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            page_token: page_token.to_owned(),
+            folders: BTreeMap::new(),
+            files: BTreeMap::new(),
+            children: HashMap::new(),
+            folder_changelog: BTreeMap::new(),
+            file_changelog: BTreeMap::new(),
+            path_changelog: BTreeMap::new(),
+            quarantine: BTreeMap::new(),
+            pending: None,
+            removed: false,
+        }
+    }
+
+    /// Apply the changes with a changelog of their own,
+    /// and keep what is needed to roll them back.
+    fn apply(&mut self, changes: &[Change], quarantine: bool) -> Savepoint {
+        let touched = self.touched(changes, quarantine);
+        let previous = self.paths(touched.iter());
+
+        let savepoint = Savepoint {
+            page_token: self.page_token.clone(),
+            items: touched
+                .into_iter()
+                .map(|id| {
+                    let item = match (self.folders.get(&id), self.files.get(&id)) {
+                        (Some(folder), _) => Some(Item::Folder(folder.clone())),
+                        (None, Some(file)) => Some(Item::File(file.clone())),
+                        (None, None) => None,
+                    };
+
+                    (id, item)
+                })
+                .collect(),
+            folder_changelog: std::mem::take(&mut self.folder_changelog),
+            file_changelog: std::mem::take(&mut self.file_changelog),
+            path_changelog: std::mem::take(&mut self.path_changelog),
+            quarantine: self.quarantine.clone(),
+        };
+
+        match quarantine {
+            true => self.apply_or_quarantine(changes),
+            false => self.apply_changes(changes),
+        }
+
+        self.record_paths(&previous);
+
+        savepoint
+    }
+
+    fn rollback(&mut self, savepoint: Savepoint) {
+        for (id, item) in savepoint.items {
+            if let Some(folder) = self.folders.remove(&id) {
+                self.remove_child(&id, folder.parent.as_deref());
+            }
+
+            if let Some(file) = self.files.remove(&id) {
+                self.remove_child(&id, Some(&file.parent));
+            }
+
+            match item {
+                Some(Item::Folder(folder)) => {
+                    self.add_child(&id, folder.parent.as_deref());
+                    self.folders.insert(id, folder);
+                }
+                Some(Item::File(file)) => {
+                    self.add_child(&id, Some(&file.parent));
+                    self.files.insert(id, file);
+                }
+                // Whatever was created within a new folder is rolled back as well.
+                None => {
+                    self.children.remove(&id);
+                }
+            }
+        }
+
+        self.page_token = savepoint.page_token;
+        self.folder_changelog = savepoint.folder_changelog;
+        self.file_changelog = savepoint.file_changelog;
+        self.path_changelog = savepoint.path_changelog;
+        self.quarantine = savepoint.quarantine;
+    }
+
+    /// The ids of the items which the changes may touch,
+    /// including everything within the folders they remove.
+    fn touched(&self, changes: &[Change], quarantine: bool) -> HashSet<String> {
+        let mut stack: Vec<(&str, bool)> = changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::DriveChanged(drive) => Some((drive.id.as_str(), false)),
+                Change::ItemChanged(item) => Some((item.id(), item.drive_id() != self.id)),
+                Change::ItemRemoved(id) => Some((id.as_str(), true)),
+                Change::DriveRemoved(_) => None,
+            })
+            .collect();
+
+        if quarantine {
+            stack.extend(self.quarantine.keys().map(|id| (id.as_str(), false)));
+        }
+
+        let mut visited = HashSet::new();
+        let mut touched = HashSet::new();
+
+        while let Some((id, removed)) = stack.pop() {
+            if !visited.insert((id, removed)) {
+                continue;
+            }
+
+            touched.insert(id.to_owned());
+
+            if removed {
+                let children = self.children.get(id).into_iter().flatten();
+                stack.extend(children.map(|child| (child.as_str(), true)));
+            }
+        }
+
+        touched
+    }
+
+    /// Apply the changes of which the parents are known, and quarantine the others until they are.
+    fn apply_or_quarantine(&mut self, changes: &[Change]) {
+        let previous = std::mem::take(&mut self.quarantine);
+
+        let since: HashMap<String, DateTime<Utc>> = previous
+            .iter()
+            .map(|(id, q)| (id.clone(), q.quarantined_at))
+            .collect();
+        let retried: Vec<Change> = previous
+            .values()
+            .map(|q| Change::ItemChanged(q.item.clone()))
+            .collect();
+
+        let changes = quarantine::retry(&retried, changes);
+
+        let existing = quarantine::parents(&changes)
+            .into_iter()
+            .filter(|parent| self.folders.contains_key(*parent))
+            .collect();
+
+        let Partition { apply, quarantined } =
+            quarantine::partition(&self.id, changes, &existing, &since);
+
+        self.apply_changes(apply);
+        self.quarantine = quarantined
+            .into_iter()
+            .map(|q| (q.item.id().to_owned(), q))
+            .collect();
+    }
+
+    fn apply_changes<'c, I>(&mut self, changes: I)
+    where
+        I: IntoIterator<Item = &'c Change>,
+    {
+        for change in changes {
+            match change {
+                Change::DriveChanged(drive) => {
+                    // The root folder of a Shared Drive shares its id with the drive.
+                    if let Some(root) = self.folders.get(&drive.id) {
+                        let root = Folder {
+                            name: drive.name.clone(),
+                            ..root.clone()
+                        };
+
+                        self.upsert_folder(root);
+                    }
+                }
+                // If an item changes to another drive_id, consider it removed.
+                Change::ItemChanged(item) if item.drive_id() != self.id => {
+                    trace!("moved to another shared drive, marked as removed");
+                    self.delete_file_or_folder(item.id());
+                }
+                Change::ItemChanged(item) => match item {
+                    Item::File(file) => self.upsert_file(file.clone()),
+                    Item::Folder(folder) => self.upsert_folder(folder.clone()),
+                },
+                Change::ItemRemoved(id) => self.delete_file_or_folder(id),
+                Change::DriveRemoved(id) => trace!(%id, "ignoring removed drive"),
+            }
+        }
+    }
+
+    // Record the changelog like the triggers of the database,
+    // where an update deletes the old and creates the new item.
+    fn upsert_folder(&mut self, folder: Folder) {
+        match self.folders.get(&folder.id) {
+            Some(old)
+                if old.name == folder.name
+                    && old.trashed == folder.trashed
+                    && old.parent == folder.parent =>
+            {
+                return
+            }
+            Some(old) => {
+                let key = ChangelogKey::new(&old.id, true);
+                self.folder_changelog
+                    .entry(key)
+                    .or_insert_with(|| old.clone());
+            }
+            None => (),
+        }
+
+        let key = ChangelogKey::new(&folder.id, false);
+        self.folder_changelog.insert(key, folder.clone());
+        let (id, parent) = (folder.id.clone(), folder.parent.clone());
+
+        if let Some(old) = self.folders.insert(folder.id.clone(), folder) {
+            self.remove_child(&old.id, old.parent.as_deref());
+        }
+
+        self.add_child(&id, parent.as_deref());
+    }
+
+    fn upsert_file(&mut self, file: File) {
+        match self.files.get(&file.id) {
+            Some(old)
+                if old.name == file.name
+                    && old.trashed == file.trashed
+                    && old.parent == file.parent
+                    && old.md5 == file.md5
+                    && old.size == file.size =>
+            {
+                return
+            }
+            Some(old) => {
+                let key = ChangelogKey::new(&old.id, true);
+                self.file_changelog
+                    .entry(key)
+                    .or_insert_with(|| old.clone());
+            }
+            None => (),
+        }
+
+        let key = ChangelogKey::new(&file.id, false);
+        self.file_changelog.insert(key, file.clone());
+        let (id, parent) = (file.id.clone(), file.parent.clone());
+
+        if let Some(old) = self.files.insert(file.id.clone(), file) {
+            self.remove_child(&old.id, Some(&old.parent));
+        }
+
+        self.add_child(&id, Some(&parent));
+    }
+
+    /// Deleting a folder also deletes everything within it.
+    fn delete_file_or_folder(&mut self, id: &str) {
+        if let Some(file) = self.files.remove(id) {
+            self.remove_child(id, Some(&file.parent));
+
+            let key = ChangelogKey::new(id, true);
+            self.file_changelog.entry(key).or_insert(file);
+        }
+
+        if let Some(folder) = self.folders.remove(id) {
+            self.remove_child(id, folder.parent.as_deref());
+
+            let key = ChangelogKey::new(id, true);
+            self.folder_changelog.entry(key).or_insert(folder);
+
+            for child in self.children.remove(id).unwrap_or_default() {
+                self.delete_file_or_folder(&child);
+            }
+        }
+    }
+
+    fn add_child(&mut self, id: &str, parent: Option<&str>) {
+        if let Some(parent) = parent {
+            let children = self.children.entry(parent.to_owned()).or_default();
+            children.insert(id.to_owned());
+        }
+    }
+
+    fn remove_child(&mut self, id: &str, parent: Option<&str>) {
+        if let Some(children) = parent.and_then(|parent| self.children.get_mut(parent)) {
+            children.remove(id);
+        }
+    }
+
+    fn clear_changelog(&mut self) {
+        self.folder_changelog.clear();
+        self.file_changelog.clear();
+        self.path_changelog.clear();
+    }
+
+    /// Every parent has to exist once the changes are committed, like the foreign keys of the database.
+    /// Only the items created by the changelog are checked, as a removed folder takes its items along.
+    fn check_parents(&self) -> Result<()> {
+        let created = |key: &&ChangelogKey| !key.deleted;
+
+        let folders = self
+            .folder_changelog
+            .keys()
+            .filter(created)
+            .filter_map(|key| self.folders.get(&key.id))
+            .filter_map(|folder| folder.parent.as_ref().map(|parent| (&folder.id, parent)));
+        let files = self
+            .file_changelog
+            .keys()
+            .filter(created)
+            .filter_map(|key| self.files.get(&key.id))
+            .map(|file| (&file.id, &file.parent));
+
+        match folders
+            .chain(files)
+            .find(|(_, parent)| !self.folders.contains_key(*parent))
+        {
+            Some((id, _)) => Err(MissingParent { id }.build().into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Count the files in the changelog which have been deleted and which have been trashed.
+    fn count_removed(&self) -> (u64, u64) {
+        let mut deleted = 0;
+        let mut trashed = 0;
+
+        for (key, old) in self.file_changelog.iter().filter(|(key, _)| key.deleted) {
+            let created = ChangelogKey::new(&key.id, false);
+
+            match self.file_changelog.get(&created) {
+                Some(new) if new.trashed && !old.trashed => trashed += 1,
+                Some(_) => (),
+                None => deleted += 1,
+            }
+        }
+
+        (deleted, trashed)
+    }
+
+    fn changed_files(&self) -> Vec<ChangedFile> {
+        self.file_changelog
+            .iter()
+            .map(|(key, file)| match key.deleted {
+                true => ChangedFile::Deleted(file.clone()),
+                false => ChangedFile::Created(file.clone()),
+            })
+            .collect()
+    }
+
+    fn changed_folders(&self) -> Vec<ChangedFolder> {
+        self.folder_changelog
+            .iter()
+            .map(|(key, folder)| match key.deleted {
+                true => ChangedFolder::Deleted(folder.clone()),
+                false => ChangedFolder::Created(folder.clone()),
+            })
+            .collect()
+    }
+
+    fn changed_paths(&self) -> Vec<ChangedPath> {
+        self.path_changelog.values().cloned().collect()
+    }
+
+    /// The paths of the items which can be reached from the root folder.
+    fn paths<'a, I>(&self, ids: I) -> HashMap<String, Path>
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        ids.into_iter()
+            .filter_map(|id| Some((id.clone(), self.path(id)?)))
+            .collect()
+    }
+
+    /// Record the paths of the changelog like the database does,
+    /// where a deleted item takes its path from before the changes and a created item its current path.
+    fn record_paths(&mut self, previous: &HashMap<String, Path>) {
+        let keys = self
+            .folder_changelog
+            .keys()
+            .chain(self.file_changelog.keys());
+
+        self.path_changelog = keys
+            .filter_map(|key| {
+                let path = match key.deleted {
+                    true => ChangedPath::Deleted(previous.get(&key.id)?.clone()),
+                    false => ChangedPath::Created(self.path(&key.id)?),
+                };
+
+                Some((key.clone(), path))
+            })
+            .collect();
+    }
+
+    /// The path of an item, unless it cannot be reached from the root folder.
+    fn path(&self, id: &str) -> Option<Path> {
+        let (folder, name, trashed, mut parent) = match (self.folders.get(id), self.files.get(id)) {
+            // The root folder is not part of any path.
+            (Some(f), _) => (true, &f.name, f.trashed, f.parent.as_ref()?),
+            (None, Some(f)) => (false, &f.name, f.trashed, &f.parent),
+            (None, None) => return None,
+        };
+
+        let mut path = format!("/{}", name);
+
+        // A cycle never reaches the root folder, so stop once every folder could have been passed.
+        for _ in 0..=self.folders.len() {
+            if parent == &self.id {
+                let inner = InnerPath {
+                    id: id.to_owned(),
+                    drive_id: self.id.clone(),
+                    path: path.into(),
+                    trashed,
+                };
+
+                return Some(match folder {
+                    true => Path::Folder(inner),
+                    false => Path::File(inner),
+                });
+            }
+
+            let folder = self.folders.get(parent)?;
+            path = format!("/{}{}", folder.name, path);
+            parent = folder.parent.as_ref()?;
+        }
+
+        None
+    }
+}
+
+/// The items touched by the changes as they were before, with the state which the changes replace.
+struct Savepoint {
+    page_token: String,
+    items: Vec<(String, Option<Item>)>,
+    folder_changelog: BTreeMap<ChangelogKey, Folder>,
+    file_changelog: BTreeMap<ChangelogKey, File>,
+    path_changelog: BTreeMap<ChangelogKey, ChangedPath>,
+    quarantine: BTreeMap<String, Quarantined>,
+}
+
+/// A copy of the changelog taken when the changes were requested.
+#[derive(Default)]
+pub struct MemoryChangelog {
+    paths: Vec<ChangedPath>,
+    files: Vec<ChangedFile>,
+    folders: Vec<ChangedFolder>,
+}
+
+#[async_trait]
+impl Changelog for MemoryChangelog {
+    async fn get_changed_files(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> Result<Vec<ChangedFile>> {
+        Ok(page(&self.files, ChangedFile::key, after, limit))
+    }
+
+    async fn get_changed_folders(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> Result<Vec<ChangedFolder>> {
+        Ok(page(&self.folders, ChangedFolder::key, after, limit))
+    }
+
+    async fn get_changed_paths(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> Result<Vec<ChangedPath>> {
+        Ok(page(&self.paths, ChangedPath::key, after, limit))
+    }
+}
+
+fn page<T: Clone>(
+    items: &[T],
+    key: fn(&T) -> ChangelogKey,
+    after: Option<&ChangelogKey>,
+    limit: i64,
+) -> Vec<T> {
+    items
+        .iter()
+        .filter(|item| !matches!(after, Some(after) if &key(item) <= after))
+        .take(limit as usize)
+        .cloned()
+        .collect()
+}
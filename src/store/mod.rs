@@ -0,0 +1,157 @@
+use crate::fetch::{Change, Item};
+use crate::model::{ChangelogKey, Drive, PendingChanges};
+use crate::{ChangedFile, ChangedFolder, ChangedPath, DeletionSummary, Preview, Result, Safeguard};
+use async_trait::async_trait;
+use chrono::Duration;
+
+mod memory;
+pub(crate) mod quarantine;
+
+pub use memory::MemoryStore;
+
+/// The persistence behind the synchronisation of Shared Drives.
+///
+/// Bernard stores its metadata in a [`DatabaseStore`] by default,
+/// whereas a [`MemoryStore`] never touches the disk.
+///
+/// Every store keeps a changelog of the items created and deleted by the last merge,
+/// where an update is both the deletion of the old and the creation of the new item.
+/// Adding a drive and merging changes replace the changelog within the same transaction,
+/// so readers never observe a changelog which has been cleared but not yet refilled.
+///
+/// [`DatabaseStore`]: crate::DatabaseStore
+#[async_trait]
+pub trait Store: Send + Sync + 'static {
+    type Changelog: Changelog;
+
+    async fn get_drive(&self, drive_id: &str) -> Result<Option<Drive>>;
+
+    /// Add a Shared Drive, its root folder and all of its items.
+    async fn add_drive(
+        &self,
+        drive_id: &str,
+        name: &str,
+        page_token: &str,
+        items: Vec<Item>,
+    ) -> Result<()>;
+
+    /// Apply the changes, replace the changelog and update the page token all at once,
+    /// or roll back when the changes delete more files than the safeguard allows.
+    ///
+    /// Items of which the parent does not exist fail the merge as a partial change list,
+    /// unless they are quarantined, in which case they are retried on every later merge.
+    ///
+    /// With a holder, the merge fails with `SyncInProgress` unless the holder still has the lease,
+    /// which is verified within the same transaction.
+    async fn merge_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        page_token: &str,
+        options: &MergeOptions<'_>,
+    ) -> Result<Merge>;
+
+    /// Remove a Shared Drive, all of its items and its pending changes.
+    ///
+    /// Afterwards the changelog holds the deletion of every item if the changes are emitted,
+    /// otherwise it is empty.
+    /// The changelog of drives which have been removed before may be cleared.
+    async fn remove_drive(&self, drive_id: &str, emit_changes: bool) -> Result<()>;
+
+    /// The changelog the changes would produce, without applying them.
+    ///
+    /// Quarantined items are left out of the changelog like they are by [`Store::merge_changes`].
+    async fn preview_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        quarantine: bool,
+    ) -> Result<Preview>;
+
+    async fn clear_changelog(&self, drive_id: &str) -> Result<()>;
+
+    /// Keep a change list which has been rejected by the safeguard, replacing the previous one.
+    async fn save_pending_changes(
+        &self,
+        drive_id: &str,
+        changes: &[Change],
+        page_token: &str,
+    ) -> Result<()>;
+
+    /// The change list which has been rejected by the safeguard, if any.
+    async fn pending_changes(&self, drive_id: &str) -> Result<Option<PendingChanges>>;
+
+    async fn clear_pending_changes(&self, drive_id: &str) -> Result<()>;
+
+    /// A snapshot of the changelog, which does not observe any later merges.
+    async fn changelog(&self, drive_id: &str) -> Result<Self::Changelog>;
+
+    /// Take or extend the lease on the synchronisation of a Shared Drive for `lease`,
+    /// unless another holder has a lease which has not expired yet.
+    ///
+    /// Returns whether the holder has the lease.
+    async fn acquire_lease(&self, drive_id: &str, holder: &str, lease: Duration) -> Result<bool>;
+
+    /// Give up the lease, if the holder still has it.
+    async fn release_lease(&self, drive_id: &str, holder: &str) -> Result<()>;
+
+    async fn close(&self) {}
+}
+
+/// A snapshot of the changelog of a Shared Drive, read one page at a time.
+///
+/// Pages are ordered by [`ChangelogKey`] and start after the given key.
+#[async_trait]
+pub trait Changelog: Send {
+    async fn get_changed_files(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> Result<Vec<ChangedFile>>;
+
+    async fn get_changed_folders(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> Result<Vec<ChangedFolder>>;
+
+    async fn get_changed_paths(
+        &mut self,
+        after: Option<&ChangelogKey>,
+        limit: i64,
+    ) -> Result<Vec<ChangedPath>>;
+}
+
+/// Turns into a [`Store`] once Bernard is built.
+#[async_trait]
+pub trait Connect {
+    type Store: Store;
+
+    async fn connect(self) -> Result<Self::Store>;
+}
+
+#[async_trait]
+impl<S: Store> Connect for S {
+    type Store = S;
+
+    async fn connect(self) -> Result<Self::Store> {
+        Ok(self)
+    }
+}
+
+/// How the changes of a synchronisation are merged by [`Store::merge_changes`].
+#[derive(Default)]
+pub struct MergeOptions<'a> {
+    pub safeguard: Option<&'a Safeguard>,
+    /// Tombstones older than the retention are pruned, or kept indefinitely without one.
+    pub retention: Option<Duration>,
+    pub quarantine: bool,
+    /// The holder of the lease on the synchronisation of the drive, if the merge requires it.
+    pub holder: Option<&'a str>,
+}
+
+/// Whether a change list has been committed, or rejected by the safeguard.
+pub enum Merge {
+    Committed,
+    Rejected(DeletionSummary),
+}
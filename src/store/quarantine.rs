@@ -0,0 +1,137 @@
+use crate::fetch::{Change, Item};
+use crate::model::Quarantined;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// The change list of a merge, in which items with an unknown parent are set aside.
+pub(crate) struct Partition<'c> {
+    /// The changes to apply, in their original order.
+    pub(crate) apply: Vec<&'c Change>,
+    pub(crate) quarantined: Vec<Quarantined>,
+}
+
+/// Retry the quarantined items along with the change list,
+/// unless the change list supersedes them with a newer change.
+pub(crate) fn retry<'c>(quarantined: &'c [Change], changes: &'c [Change]) -> Vec<&'c Change> {
+    let superseded: HashSet<&str> = changes.iter().filter_map(item_id).collect();
+
+    quarantined
+        .iter()
+        .filter(|change| !matches!(item_id(change), Some(id) if superseded.contains(id)))
+        .chain(changes)
+        .collect()
+}
+
+/// The parents the changes rely on, of which the store has to tell whether they exist.
+pub(crate) fn parents<'c>(changes: &[&'c Change]) -> BTreeSet<&'c str> {
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            Change::ItemChanged(item) => parent(item),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Quarantine the items which would not have a parent once the changes are applied,
+/// including the items within a quarantined folder.
+///
+/// Only the last change of an item decides where it ends up,
+/// so all changes of a quarantined item are set aside.
+pub(crate) fn partition<'c>(
+    drive_id: &str,
+    changes: Vec<&'c Change>,
+    existing: &HashSet<&str>,
+    since: &HashMap<String, DateTime<Utc>>,
+) -> Partition<'c> {
+    let mut last: HashMap<&str, &Change> = HashMap::new();
+
+    for &change in &changes {
+        if let Some(id) = item_id(change) {
+            last.insert(id, change);
+        }
+    }
+
+    // The folders which exist once the changes are applied, unless they are quarantined.
+    let mut folders: HashSet<&str> = existing.clone();
+
+    for (&id, change) in &last {
+        match change {
+            Change::ItemChanged(Item::Folder(folder)) if folder.drive_id == drive_id => {
+                folders.insert(id)
+            }
+            _ => folders.remove(id),
+        };
+    }
+
+    let mut reasons: HashMap<&str, String> = HashMap::new();
+
+    loop {
+        let orphans: Vec<(&str, String)> = last
+            .values()
+            .filter_map(|change| match change {
+                Change::ItemChanged(item) if item.drive_id() == drive_id => Some(item),
+                _ => None,
+            })
+            .filter(|item| !reasons.contains_key(item.id()))
+            .filter_map(|item| {
+                let parent = parent(item)?;
+
+                match (folders.contains(parent), reasons.contains_key(parent)) {
+                    (true, _) => None,
+                    (false, true) => Some(format!("the parent {} is quarantined", parent)),
+                    (false, false) => Some(format!("the parent {} is unknown", parent)),
+                }
+                .map(|reason| (item.id(), reason))
+            })
+            .collect();
+
+        if orphans.is_empty() {
+            break;
+        }
+
+        for (id, reason) in orphans {
+            folders.remove(id);
+            reasons.insert(id, reason);
+        }
+    }
+
+    let now = Utc::now();
+
+    let quarantined = reasons
+        .iter()
+        .filter_map(|(id, reason)| match last[id] {
+            Change::ItemChanged(item) => Some(Quarantined {
+                item: item.clone(),
+                reason: reason.clone(),
+                quarantined_at: since.get(*id).copied().unwrap_or(now),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    let apply = changes
+        .into_iter()
+        .filter(|change| match change {
+            Change::ItemChanged(item) => !reasons.contains_key(item.id()),
+            _ => true,
+        })
+        .collect();
+
+    Partition { apply, quarantined }
+}
+
+fn parent(item: &Item) -> Option<&str> {
+    match item {
+        Item::File(file) => Some(&file.parent),
+        Item::Folder(folder) => folder.parent.as_deref(),
+    }
+}
+
+fn item_id(change: &Change) -> Option<&str> {
+    match change {
+        Change::ItemChanged(item) => Some(item.id()),
+        Change::ItemRemoved(id) => Some(id),
+        _ => None,
+    }
+}
@@ -0,0 +1,54 @@
+use bernard::{Connect, DatabaseOptions, ErrorKind, Folder, Item, Snapshot, Store};
+
+const DRIVE_ID: &str = "diff-test";
+
+/// The path or URL of the database, and whether it is a file of this test.
+#[cfg(not(feature = "postgres"))]
+fn database_path() -> (String, bool) {
+    let path = std::env::temp_dir().join(format!("bernard-diff-{}.db", std::process::id()));
+    (path.to_string_lossy().into_owned(), true)
+}
+
+#[cfg(feature = "postgres")]
+fn database_path() -> (String, bool) {
+    let url = std::env::var("BERNARD_TEST_DATABASE_URL")
+        .expect("BERNARD_TEST_DATABASE_URL must point to a Postgres database");
+
+    (url, false)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn snapshot_of_unknown_drive_fails() {
+    let (path, temporary) = database_path();
+    let store = DatabaseOptions::new(&path).connect().await.unwrap();
+
+    store.remove_drive(DRIVE_ID, false).await.unwrap();
+
+    let items = vec![Item::Folder(Folder {
+        id: "movies".to_owned(),
+        drive_id: DRIVE_ID.to_owned(),
+        name: "Movies".to_owned(),
+        trashed: false,
+        parent: Some(DRIVE_ID.to_owned()),
+    })];
+
+    store
+        .add_drive(DRIVE_ID, "Drive", "1", items)
+        .await
+        .unwrap();
+
+    let snapshot = Snapshot::from_database(&path, DRIVE_ID).await.unwrap();
+    assert_eq!(snapshot.records().len(), 2);
+
+    let error = Snapshot::from_database(&path, "typo").await.unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::UnknownDrive);
+
+    store.remove_drive(DRIVE_ID, false).await.unwrap();
+    store.close().await;
+
+    if temporary {
+        for suffix in &["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+    }
+}
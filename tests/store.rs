@@ -0,0 +1,292 @@
+use bernard::{
+    Change, Changelog, ChangelogKey, Connect, DatabaseOptions, ErrorKind, File, Folder, Item,
+    MemoryStore, MergeOptions, PartialDrive, Store,
+};
+use chrono::Duration;
+
+const DRIVE_ID: &str = "store-test";
+
+/// The database of the tests, which is a Postgres database with the `postgres` feature.
+#[cfg(not(feature = "postgres"))]
+fn database() -> DatabaseOptions {
+    DatabaseOptions::new(":memory:")
+}
+
+#[cfg(feature = "postgres")]
+fn database() -> DatabaseOptions {
+    let url = std::env::var("BERNARD_TEST_DATABASE_URL")
+        .expect("BERNARD_TEST_DATABASE_URL must point to a Postgres database");
+
+    DatabaseOptions::new(&url)
+}
+
+fn folder(id: &str, parent: &str, name: &str) -> Change {
+    Change::ItemChanged(Item::Folder(Folder {
+        id: id.to_owned(),
+        drive_id: DRIVE_ID.to_owned(),
+        name: name.to_owned(),
+        trashed: false,
+        parent: Some(parent.to_owned()),
+    }))
+}
+
+fn file(id: &str, parent: &str, name: &str, size: i64) -> Change {
+    Change::ItemChanged(Item::File(File {
+        id: id.to_owned(),
+        drive_id: DRIVE_ID.to_owned(),
+        name: name.to_owned(),
+        trashed: false,
+        parent: parent.to_owned(),
+        md5: format!("md5-{}", size),
+        size,
+    }))
+}
+
+fn trashed(change: Change) -> Change {
+    match change {
+        Change::ItemChanged(Item::File(file)) => Change::ItemChanged(Item::File(File {
+            trashed: true,
+            ..file
+        })),
+        Change::ItemChanged(Item::Folder(folder)) => Change::ItemChanged(Item::Folder(Folder {
+            trashed: true,
+            ..folder
+        })),
+        change => change,
+    }
+}
+
+fn removed(id: &str) -> Change {
+    Change::ItemRemoved(id.to_owned())
+}
+
+fn items(changes: Vec<Change>) -> Vec<Item> {
+    changes
+        .into_iter()
+        .filter_map(|change| match change {
+            Change::ItemChanged(item) => Some(item),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Read every page of the changelog, two changes at a time.
+async fn read_changelog<S: Store>(store: &S) -> Vec<String> {
+    let mut changelog = store.changelog(DRIVE_ID).await.unwrap();
+    let mut changes = Vec::new();
+
+    let mut after: Option<ChangelogKey> = None;
+    loop {
+        let page = changelog
+            .get_changed_folders(after.as_ref(), 2)
+            .await
+            .unwrap();
+        match page.last() {
+            Some(last) => after = Some(last.key()),
+            None => break,
+        }
+        changes.extend(page.iter().map(|change| format!("{:?}", change)));
+    }
+
+    let mut after: Option<ChangelogKey> = None;
+    loop {
+        let page = changelog
+            .get_changed_files(after.as_ref(), 2)
+            .await
+            .unwrap();
+        match page.last() {
+            Some(last) => after = Some(last.key()),
+            None => break,
+        }
+        changes.extend(page.iter().map(|change| format!("{:?}", change)));
+    }
+
+    let mut keys = Vec::new();
+    let mut after: Option<ChangelogKey> = None;
+    loop {
+        let page = changelog
+            .get_changed_paths(after.as_ref(), 2)
+            .await
+            .unwrap();
+        match page.last() {
+            Some(last) => after = Some(last.key()),
+            None => break,
+        }
+        keys.extend(page.iter().map(|change| change.key()));
+        changes.extend(page.iter().map(|change| format!("{:?}", change)));
+    }
+
+    let mut unique = keys.clone();
+    unique.dedup();
+    assert_eq!(keys, unique, "the paths of the changelog repeat a key");
+
+    changes
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn memory_store_matches_database_store() {
+    let database = database().connect().await.unwrap();
+    let memory = MemoryStore::new();
+
+    // Start from scratch, as a Postgres database outlives the test.
+    database.remove_drive(DRIVE_ID, false).await.unwrap();
+
+    let tree = items(vec![
+        folder("tv", DRIVE_ID, "TV"),
+        folder("show", "tv", "Show"),
+        folder("season", "show", "Season 1"),
+        file("e1", "season", "e1.mkv", 100),
+        file("e2", "season", "e2.mkv", 200),
+        folder("movies", DRIVE_ID, "Movies"),
+        file("m1", "movies", "m1.mkv", 1000),
+        file("m2", "movies", "m2.mkv", 1000),
+    ]);
+
+    for store in [&database as &dyn DynStore, &memory] {
+        store.add(tree.clone()).await;
+    }
+
+    assert_eq!(
+        read_changelog(&database).await,
+        read_changelog(&memory).await
+    );
+
+    let merges = vec![
+        // Rename a folder and a file within it.
+        vec![
+            folder("show", "tv", "The Show"),
+            file("e1", "season", "e1 (1).mkv", 100),
+        ],
+        // Move a folder, modify a file and create another within it.
+        vec![
+            folder("season", "movies", "Season 1"),
+            file("e2", "season", "e2.mkv", 201),
+            file("e3", "season", "e3.mkv", 300),
+        ],
+        // Trash a file and remove a folder with everything within it.
+        vec![
+            trashed(file("m1", "movies", "m1.mkv", 1000)),
+            removed("season"),
+        ],
+        // Move a file to another Shared Drive and rename the drive.
+        vec![
+            Change::ItemChanged(Item::File(File {
+                id: "m2".to_owned(),
+                drive_id: "elsewhere".to_owned(),
+                name: "m2.mkv".to_owned(),
+                trashed: false,
+                parent: "elsewhere".to_owned(),
+                md5: "md5-1000".to_owned(),
+                size: 1000,
+            })),
+            Change::DriveChanged(PartialDrive {
+                id: DRIVE_ID.to_owned(),
+                name: "Renamed".to_owned(),
+            }),
+        ],
+    ];
+
+    for changes in merges {
+        for store in [&database as &dyn DynStore, &memory] {
+            store.merge(&changes).await;
+        }
+
+        assert_eq!(
+            read_changelog(&database).await,
+            read_changelog(&memory).await
+        );
+    }
+
+    database.remove_drive(DRIVE_ID, true).await.unwrap();
+    memory.remove_drive(DRIVE_ID, true).await.unwrap();
+    assert_eq!(
+        read_changelog(&database).await,
+        read_changelog(&memory).await
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merge_requires_the_lease() {
+    let database = database().connect().await.unwrap();
+
+    merge_with_lease(&database).await;
+    merge_with_lease(&MemoryStore::new()).await;
+}
+
+async fn merge_with_lease<S: Store>(store: &S) {
+    let drive_id = "store-lease-test";
+
+    // Start from scratch, as a Postgres database outlives the test.
+    store.remove_drive(drive_id, false).await.unwrap();
+    store.release_lease(drive_id, "a").await.unwrap();
+    store
+        .add_drive(drive_id, "Drive", "1", vec![])
+        .await
+        .unwrap();
+
+    assert_eq!(
+        merge(store, drive_id, "a").await,
+        Err(ErrorKind::SyncInProgress)
+    );
+
+    let lease = Duration::minutes(1);
+    assert!(store.acquire_lease(drive_id, "a", lease).await.unwrap());
+    assert_eq!(
+        merge(store, drive_id, "b").await,
+        Err(ErrorKind::SyncInProgress)
+    );
+    assert_eq!(merge(store, drive_id, "a").await, Ok(()));
+
+    // An expired lease may be taken over by another holder at any time.
+    let expired = Duration::milliseconds(-1);
+    assert!(store.acquire_lease(drive_id, "a", expired).await.unwrap());
+    assert_eq!(
+        merge(store, drive_id, "a").await,
+        Err(ErrorKind::SyncInProgress)
+    );
+
+    let drive = store.get_drive(drive_id).await.unwrap().unwrap();
+    assert_eq!(drive.page_token, "2");
+
+    store.release_lease(drive_id, "a").await.unwrap();
+    store.remove_drive(drive_id, false).await.unwrap();
+    store.close().await;
+}
+
+/// Rename the drive with the lease of the holder.
+async fn merge<S: Store>(store: &S, drive_id: &str, holder: &str) -> Result<(), ErrorKind> {
+    let changes = [Change::DriveChanged(PartialDrive {
+        id: drive_id.to_owned(),
+        name: "Renamed".to_owned(),
+    })];
+
+    let options = MergeOptions {
+        holder: Some(holder),
+        ..MergeOptions::default()
+    };
+
+    match store.merge_changes(drive_id, &changes, "2", &options).await {
+        Ok(_) => Ok(()),
+        Err(error) => Err(error.kind()),
+    }
+}
+
+/// Apply the same steps to both stores, of which the changelogs are compared.
+#[async_trait::async_trait]
+trait DynStore: Sync {
+    async fn add(&self, items: Vec<Item>);
+    async fn merge(&self, changes: &[Change]);
+}
+
+#[async_trait::async_trait]
+impl<S: Store> DynStore for S {
+    async fn add(&self, items: Vec<Item>) {
+        self.add_drive(DRIVE_ID, "Drive", "1", items).await.unwrap();
+    }
+
+    async fn merge(&self, changes: &[Change]) {
+        self.merge_changes(DRIVE_ID, changes, "2", &MergeOptions::default())
+            .await
+            .unwrap();
+    }
+}